@@ -0,0 +1,162 @@
+// The board representation BoardState used before groups were tracked incrementally. Groups and
+// liberties are found with a flood fill after every move. Only the rules needed to play out a
+// game under Japanese rules are kept, so the two can be compared move for move
+use cgmath::{point2, Point2};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    Black,
+    White,
+}
+
+fn other_player(player: Player) -> Player {
+    match player {
+        Player::Black => Player::White,
+        Player::White => Player::Black,
+    }
+}
+
+pub struct BoardState {
+    pub width: usize,
+    pub height: usize,
+    pub current_player: Player,
+    pub board: Vec<Vec<Option<Player>>>,
+    pub ko: Option<Point2<usize>>,
+    pub captured_black: u32,
+    pub captured_white: u32,
+}
+
+impl BoardState {
+    pub fn new(width: usize, height: usize) -> BoardState {
+        BoardState {
+            width,
+            height,
+            current_player: Player::Black,
+            board: vec![vec![None; height]; width],
+            ko: None,
+            captured_black: 0,
+            captured_white: 0,
+        }
+    }
+
+    pub fn try_play(self: &mut BoardState, point: Point2<usize>) -> Result<(), &str> {
+        if self.board[point.x][point.y].is_some() {
+            return Err("Can't play where a piece already is");
+        }
+
+        if let Some(ko) = self.ko {
+            if point == ko {
+                return Err("Can't play in the ko");
+            }
+        }
+
+        self.board[point.x][point.y] = Some(self.current_player);
+
+        let mut captured = vec![];
+        for neighbor in self.get_neighbours(&point) {
+            if self.board[neighbor.x][neighbor.y] != Some(other_player(self.current_player)) {
+                continue;
+            }
+
+            let line = self.get_line(&neighbor);
+            let liberties = self.get_liberties(&line);
+            if liberties.is_empty() {
+                for point in line {
+                    self.board[point.x][point.y] = None;
+                    captured.push(point);
+                    if self.current_player == Player::Black {
+                        self.captured_white += 1;
+                    } else {
+                        self.captured_black += 1;
+                    }
+                }
+            }
+        }
+
+        let played_line = self.get_line(&point);
+        let liberties = self.get_liberties(&played_line);
+        if liberties.is_empty() {
+            self.board[point.x][point.y] = None;
+            return Err("Self capture");
+        }
+
+        self.ko = None;
+        if captured.len() == 1 && played_line.len() == 1 && liberties.len() == 1 {
+            self.ko = Some(captured[0]);
+        }
+
+        self.current_player = other_player(self.current_player);
+        return Ok(());
+    }
+
+    pub fn pass(self: &mut BoardState) {
+        self.ko = None;
+        self.current_player = other_player(self.current_player);
+    }
+
+    pub fn get_line(self: &mut BoardState, point: &Point2<usize>) -> Vec<Point2<usize>> {
+        let color = self.board[point.x][point.y];
+        if color.is_none() {
+            return vec![];
+        }
+
+        let mut to_visit = vec![];
+        let mut visited = HashSet::new();
+        let mut line = vec![];
+
+        to_visit.push(*point);
+
+        while let Some(current) = to_visit.pop() {
+            visited.insert(current);
+
+            if self.board[current.x][current.y] != color {
+                continue;
+            }
+
+            line.push(current);
+            let neighbors = self.get_neighbours(&current);
+            for neighbor in neighbors {
+                if !visited.contains(&neighbor) {
+                    to_visit.push(neighbor);
+                }
+            }
+        }
+
+        return line;
+    }
+
+    pub fn get_liberties(self: &mut BoardState, line: &Vec<Point2<usize>>) -> Vec<Point2<usize>> {
+        let mut liberties = HashSet::new();
+
+        for point in line {
+            let neighbors = self.get_neighbours(point);
+            for neighbor in neighbors {
+                if self.board[neighbor.x][neighbor.y].is_none() {
+                    liberties.insert(neighbor);
+                }
+            }
+        }
+
+        return liberties.into_iter().collect();
+    }
+
+    pub fn get_neighbours(self: &mut BoardState, point: &Point2<usize>) -> Vec<Point2<usize>> {
+        let mut neighbours = vec![];
+
+        if point.x > 0 {
+            neighbours.push(point2(point.x - 1, point.y));
+        }
+        if point.x < self.width - 1 {
+            neighbours.push(point2(point.x + 1, point.y));
+        }
+        if point.y > 0 {
+            neighbours.push(point2(point.x, point.y - 1));
+        }
+        if point.y < self.height - 1 {
+            neighbours.push(point2(point.x, point.y + 1));
+        }
+
+        return neighbours;
+    }
+}
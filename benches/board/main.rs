@@ -0,0 +1,135 @@
+// Times random games on BoardState against the flood fill board it replaced. Run with
+// `cargo bench`, or `make bench`
+#![allow(dead_code)]
+
+// Checking the bench builds go.rs's tests module without its test functions, so the imports
+// they use look unused
+#[allow(unused_imports)]
+#[path = "../../src/go.rs"]
+mod go;
+#[path = "../../src/rules.rs"]
+mod rules;
+
+mod flood_fill;
+
+use cgmath::{point2, Point2};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// The board operations a random game needs, so both representations can play the same games
+trait Board {
+    fn new(width: usize, height: usize) -> Self;
+    fn play(&mut self, point: Point2<usize>) -> bool;
+    fn pass(&mut self);
+    fn captures(&self) -> u32;
+}
+
+impl Board for go::BoardState {
+    fn new(width: usize, height: usize) -> Self {
+        go::BoardState::new(width, height)
+    }
+
+    fn play(&mut self, point: Point2<usize>) -> bool {
+        self.try_play(point).is_ok()
+    }
+
+    fn pass(&mut self) {
+        // Keep playing after two passes, the same as the flood fill board
+        self.consecutive_passes = 0;
+        go::BoardState::pass(self).unwrap();
+    }
+
+    fn captures(&self) -> u32 {
+        self.captured_black + self.captured_white
+    }
+}
+
+impl Board for flood_fill::BoardState {
+    fn new(width: usize, height: usize) -> Self {
+        flood_fill::BoardState::new(width, height)
+    }
+
+    fn play(&mut self, point: Point2<usize>) -> bool {
+        self.try_play(point).is_ok()
+    }
+
+    fn pass(&mut self) {
+        flood_fill::BoardState::pass(self)
+    }
+
+    fn captures(&self) -> u32 {
+        self.captured_black + self.captured_white
+    }
+}
+
+// A small xorshift generator, so every run plays the same games
+struct Random(u64);
+
+impl Random {
+    fn below(&mut self, limit: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % limit as u64) as usize
+    }
+}
+
+// Plays a game of random moves, passing whenever a few random points in a row are illegal.
+// Returns the number of moves played and stones captured
+fn random_game<B: Board>(width: usize, height: usize, seed: u64) -> (u32, u32) {
+    let mut board = B::new(width, height);
+    let mut random = Random(seed);
+    let mut moves = 0;
+
+    for _ in 0..width * height * 3 {
+        let played = (0..8).any(|_| {
+            let point = point2(random.below(width), random.below(height));
+            board.play(black_box(point))
+        });
+        if played {
+            moves += 1;
+        } else {
+            board.pass();
+        }
+    }
+
+    return (moves, board.captures());
+}
+
+// Plays the same games on a board, returning the time taken, the moves played and the captures
+fn time_games<B: Board>(width: usize, height: usize, games: u64) -> (Duration, u32, u32) {
+    let start = Instant::now();
+    let (mut moves, mut captures) = (0, 0);
+    for seed in 1..=games {
+        let (game_moves, game_captures) = random_game::<B>(width, height, seed);
+        moves += game_moves;
+        captures += game_captures;
+    }
+
+    return (start.elapsed(), moves, captures);
+}
+
+fn main() {
+    for (size, games) in [(9, 400), (13, 150), (19, 50)] {
+        let (flood_fill_time, moves, _) = time_games::<flood_fill::BoardState>(size, size, games);
+        let (union_find_time, union_find_moves, captures) =
+            time_games::<go::BoardState>(size, size, games);
+
+        // Both boards see the same random numbers, so they only agree if every move had the same
+        // legality. The captures aren't compared, as the flood fill counts some stones twice
+        assert_eq!(moves, union_find_moves, "The boards played different games");
+
+        let per_move = |time: Duration| time.as_nanos() as f64 / moves as f64;
+        println!(
+            "{}x{}: {} games, {} moves, {} captures. Flood fill {:.0}ns/move, union-find {:.0}ns/move ({:.1}x faster)",
+            size,
+            size,
+            games,
+            moves,
+            captures,
+            per_move(flood_fill_time),
+            per_move(union_find_time),
+            flood_fill_time.as_secs_f64() / union_find_time.as_secs_f64(),
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use crate::game_tree::GameTree;
+use crate::go::GamePhase;
+use crate::library;
+use crate::settings::GameSettings;
+use crate::sgf;
+use crate::utility::home_directory;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Private SGF properties on the root of the record. The menu settings say how to carry on the
+// game, and the cursor is the node being played from, counted in the order the file lists them
+const SETTINGS_PROPERTY: &str = "XSETTINGS";
+const CURSOR_PROPERTY: &str = "XCURSOR";
+
+// Where the app keeps its files, under the home directory of the device. The functions below
+// take it as an argument, so the tests can each keep their games somewhere of their own
+pub fn data_directory() -> PathBuf {
+    return home_directory().join(".local/share/gomarkable");
+}
+
+fn path(directory: &Path) -> PathBuf {
+    return directory.join("autosave.sgf");
+}
+
+// Keeps the menu settings a game was started with on the root of its record
+pub fn record_settings(tree: &mut GameTree, settings: &GameSettings) {
+    let properties = &mut tree.root_mut().properties;
+    properties.retain(|(name, _)| name != SETTINGS_PROPERTY);
+    properties.push((SETTINGS_PROPERTY.to_string(), settings.to_lines()));
+}
+
+pub fn settings(tree: &GameTree) -> GameSettings {
+    let values = tree
+        .root()
+        .properties
+        .iter()
+        .find(|(name, _)| name == SETTINGS_PROPERTY)
+        .map(|(_, values)| values.as_slice())
+        .unwrap_or_default();
+    return GameSettings::from_lines(values.iter().map(|value| value.as_str()));
+}
+
+// The position of a node when the tree is written out, which is also its index once read back
+fn written_position(tree: &GameTree, node: usize) -> usize {
+    let mut to_visit = vec![0];
+    let mut position = 0;
+    while let Some(index) = to_visit.pop() {
+        if index == node {
+            break;
+        }
+        position += 1;
+        to_visit.extend(tree.node(index).children.iter().rev());
+    }
+    return position;
+}
+
+fn to_sgf(tree: &mut GameTree) -> String {
+    let cursor = written_position(tree, tree.current());
+    tree.root_mut()
+        .set_property(CURSOR_PROPERTY, &cursor.to_string());
+    return sgf::write(tree);
+}
+
+fn from_sgf(text: &str) -> Result<GameTree, String> {
+    let mut tree = sgf::read(text)?;
+    let cursor = tree
+        .root()
+        .property(CURSOR_PROPERTY)
+        .and_then(|x| x.parse().ok());
+    if let Some(cursor) = cursor {
+        tree.go_to(cursor)
+            .map_err(|error| format!("The saved position can't be reached: {}", error))?;
+    }
+    return Ok(tree);
+}
+
+// Writes the game to disk so it can be resumed if the app is closed. Nothing is kept while
+// handicap stones are being placed, and once the game has finished it moves to the library
+pub fn save(tree: &mut GameTree, directory: &Path) -> io::Result<()> {
+    match tree.current_state().phase {
+        GamePhase::Setup => Ok(()),
+        GamePhase::Finished => library::add(tree, directory).and_then(|_| remove(directory)),
+        GamePhase::Playing | GamePhase::Scoring => write(directory, &to_sgf(tree)),
+    }
+}
+
+// The file is written in full before it replaces the last save, so being killed part way
+// through never leaves half a game behind
+fn write(directory: &Path, sgf: &str) -> io::Result<()> {
+    let path = path(directory);
+    let partial = path.with_extension("sgf.partial");
+    fs::create_dir_all(directory)?;
+    fs::write(&partial, sgf)?;
+    return fs::rename(partial, path);
+}
+
+fn remove(directory: &Path) -> io::Result<()> {
+    match fs::remove_file(path(directory)) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+pub fn exists(directory: &Path) -> bool {
+    return path(directory).exists();
+}
+
+// Reads the saved game, with the cursor back where it was, and the settings it was started with
+pub fn load(directory: &Path) -> Result<(GameTree, GameSettings), String> {
+    let text = fs::read_to_string(path(directory))
+        .map_err(|error| format!("The saved game couldn't be read: {}", error))?;
+    let tree = from_sgf(&text)?;
+    let settings = settings(&tree);
+    return Ok((tree, settings));
+}
+
+// An empty directory for one test to save its games in, so tests running at the same time don't
+// write over each other's files
+#[cfg(test)]
+pub fn test_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join("gomarkable-tests").join(name);
+    let _ = fs::remove_dir_all(&directory);
+    return directory;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_tree::GameTree;
+    use crate::go::{BoardState, Player};
+    use crate::settings::{GameSettings, Mode};
+    use cgmath::point2;
+
+    #[test]
+    fn resume_on_variation() {
+        let mut tree = GameTree::new(BoardState::new(9, 9));
+        let mut settings = GameSettings {
+            mode: Mode::OnePlayer,
+            ..GameSettings::default()
+        };
+        settings.set("Board Size", "9x9").unwrap();
+        super::record_settings(&mut tree, &settings);
+
+        tree.play(point2(2, 2)).unwrap();
+        tree.play(point2(6, 6)).unwrap();
+        tree.go_to_parent();
+        tree.play(point2(6, 2)).unwrap();
+        tree.play(point2(2, 6)).unwrap();
+        let state = tree.current_state().clone();
+
+        let resumed = super::from_sgf(&super::to_sgf(&mut tree)).unwrap();
+        assert_eq!(resumed.move_number(), 3);
+        assert!(resumed.current_state() == &state);
+        assert_eq!(resumed.current_state().current_player, Player::White);
+
+        assert_eq!(super::settings(&resumed), settings);
+    }
+
+    #[test]
+    fn resume_without_cursor() {
+        let resumed = super::from_sgf("(;SZ[9];B[cc];W[gg])").unwrap();
+        assert_eq!(resumed.current(), 0);
+        assert_eq!(super::settings(&resumed), GameSettings::default());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::cgmath_extensions::Decomposable;
 use crate::game_controller::GameController;
-use crate::ui::{UiComponent, UiController};
+use crate::go::GamePhase;
+use crate::ui::{UiComponent, UiController, UiEvent};
 use crate::{go, ui};
 use cgmath::{point2, vec2, Array, ElementWise, EuclideanSpace, Point2, Vector2};
 use libremarkable::appctx::ApplicationContext;
@@ -14,7 +15,8 @@ use std::cmp::min;
 use std::rc::Rc;
 
 pub struct BoardUi {
-    size: usize,
+    width: usize,
+    height: usize,
     board_start: Point2<i32>,
     board_size: Vector2<i32>,
     square_size: Vector2<i32>,
@@ -23,40 +25,43 @@ pub struct BoardUi {
     stone_radius: u32,
 }
 
-fn hoshi_points(size: usize) -> Vec<Point2<usize>> {
-    match size {
-        19 => vec![
-            point2(3, 3),
-            point2(9, 3),
-            point2(15, 3),
-            point2(3, 9),
-            point2(9, 9),
-            point2(15, 9),
-            point2(3, 15),
-            point2(9, 15),
-            point2(15, 15),
-        ],
-        13 => vec![
-            point2(3, 3),
-            point2(10, 3),
-            point2(6, 6),
-            point2(3, 10),
-            point2(10, 10),
-        ],
-        9 => vec![
-            point2(2, 2),
-            point2(6, 2),
-            point2(4, 4),
-            point2(2, 6),
-            point2(6, 6),
-        ],
+// The lines which have star points along one side of the board
+fn hoshi_lines(length: usize) -> Vec<usize> {
+    let middle = match length % 2 {
+        1 if length >= 5 => vec![length / 2],
         _ => vec![],
+    };
+    if length < 7 {
+        return middle;
     }
+
+    let edge_distance = if length >= 13 { 3 } else { 2 };
+    let mut lines = vec![edge_distance, length - 1 - edge_distance];
+    lines.extend(middle);
+    return lines;
+}
+
+fn hoshi_points(width: usize, height: usize) -> Vec<Point2<usize>> {
+    let is_middle = |line: usize, length: usize| length % 2 == 1 && line == length / 2;
+    // Only large boards have star points on the sides, smaller ones have the corners and center
+    let has_sides = width >= 15 && height >= 15;
+
+    let mut points = vec![];
+    for x in hoshi_lines(width) {
+        for y in hoshi_lines(height) {
+            let side = is_middle(x, width) != is_middle(y, height);
+            if !side || has_sides {
+                points.push(point2(x, y));
+            }
+        }
+    }
+
+    return points;
 }
 
 impl BoardUi {
-    pub fn new(ctx: &ApplicationContext, size: usize) -> BoardUi {
-        let minimum_border = match size {
+    pub fn new(ctx: &ApplicationContext, width: usize, height: usize) -> BoardUi {
+        let minimum_border = match width.max(height) {
             size if size > 13 => 100i32,
             _ => 150i32,
         };
@@ -68,18 +73,25 @@ impl BoardUi {
         let (screen_height, screen_width) = ctx.get_dimensions();
         let screen_size = vec2(screen_width as i32, screen_height as i32);
 
-        let square_dimension =
-            (min(screen_width, screen_height) as i32 - (minimum_border * 2) - line_width as i32)
-                / (size - 1) as i32;
+        // Tall boards are limited by the space between the player bars and game controls, which
+        // also has to fit the margin of one square cleared around the board
+        let vertical_space = screen_height as i32 - 2 * 224;
+        let square_dimension = min(
+            (screen_width as i32 - (minimum_border * 2) - line_width as i32) / (width - 1) as i32,
+            (vertical_space - line_width as i32) / (height + 1) as i32,
+        );
         let square_size = Vector2::from_value(square_dimension);
         let stone_radius = ((square_dimension - stone_gap) / 2) as u32;
 
-        let board_dimension = square_dimension * (size as i32 - 1) + line_width as i32;
-        let board_size = Vector2::from_value(board_dimension);
+        let board_size = vec2(
+            square_dimension * (width as i32 - 1) + line_width as i32,
+            square_dimension * (height as i32 - 1) + line_width as i32,
+        );
         let board_start = Point2::from_vec((screen_size - board_size) / 2);
 
         BoardUi {
-            size,
+            width,
+            height,
             board_start,
             board_size,
             square_size,
@@ -101,9 +113,18 @@ impl UiComponent<Box<dyn GameController>> for BoardUi {
         self: &mut BoardUi,
         _ui: Rc<RefCell<&mut UiController>>,
         state: &mut Box<dyn GameController>,
-        event: &InputEvent,
+        event: &UiEvent,
     ) {
-        if let InputEvent::MultitouchEvent { event, .. } = event {
+        // Anything finished in the background, such as the computer's move, is picked up once for
+        // the whole scene
+        if *event == UiEvent::Wake {
+            if state.poll() {
+                ui::post_redraw();
+            }
+            return;
+        }
+
+        if let UiEvent::Input(InputEvent::MultitouchEvent { event, .. }) = event {
             // TODO show a ghost square on press/move, and play on release
             if let MultitouchEvent::Press { finger } = event {
                 let board_position =
@@ -115,13 +136,24 @@ impl UiComponent<Box<dyn GameController>> for BoardUi {
                 );
 
                 if point.x >= 0
-                    && point.x < self.size as i32
+                    && point.x < self.width as i32
                     && point.y >= 0
-                    && point.y < self.size as i32
+                    && point.y < self.height as i32
                 {
-                    let legal_move = state.try_play(point.cast().unwrap()).is_ok();
+                    // Before the game tapping places handicap stones, and while scoring it marks
+                    // groups dead or alive. A refused move still redraws, to show the reason
+                    let changed = match state.game_phase() {
+                        GamePhase::Setup => {
+                            state.toggle_handicap_stone(point.cast().unwrap()).is_ok()
+                        }
+                        GamePhase::Scoring => state.toggle_dead(point.cast().unwrap()).is_ok(),
+                        _ => {
+                            let _ = state.try_play(point.cast().unwrap());
+                            true
+                        }
+                    };
 
-                    if legal_move {
+                    if changed {
                         ui::post_redraw();
                     }
                 }
@@ -150,18 +182,20 @@ impl UiComponent<Box<dyn GameController>> for BoardUi {
             color::BLACK,
         );
 
-        for i in 1..(self.size - 1) {
-            // Draw the vertical lines
-            let vertical_start = self.board_to_screen(point2(i, 0));
+        // Draw the vertical lines
+        for x in 1..(self.width - 1) {
+            let vertical_start = self.board_to_screen(point2(x, 0));
             fb.draw_line(
                 vertical_start,
                 vertical_start + self.board_size.y_component(),
                 self.line_width,
                 color::BLACK,
             );
+        }
 
-            // Draw the horizontal lines
-            let horizontal_start = self.board_to_screen(point2(0, i));
+        // Draw the horizontal lines
+        for y in 1..(self.height - 1) {
+            let horizontal_start = self.board_to_screen(point2(0, y));
             fb.draw_line(
                 horizontal_start,
                 horizontal_start + self.board_size.x_component(),
@@ -171,16 +205,16 @@ impl UiComponent<Box<dyn GameController>> for BoardUi {
         }
 
         // Draw star points
-        for point in hoshi_points(self.size) {
+        for point in hoshi_points(self.width, self.height) {
             fb.fill_circle(self.board_to_screen(point), self.hoshi_radius, color::BLACK);
         }
 
         // Draw the stones
-        for x in 0..self.size {
-            for y in 0..self.size {
+        for x in 0..self.width {
+            for y in 0..self.height {
                 let position = self.board_to_screen(point2(x, y));
                 // TODO Both of these need aliasing!
-                match board.board[x][y] {
+                match board.stone_at(point2(x, y)) {
                     Some(go::Player::Black) => {
                         fb.fill_circle(position.into(), self.stone_radius, color::BLACK);
                     }
@@ -219,6 +253,31 @@ impl UiComponent<Box<dyn GameController>> for BoardUi {
             );
         }
 
+        // Draw territory, which includes any dead stones
+        if matches!(board.phase, GamePhase::Scoring | GamePhase::Finished) {
+            let size = vec2(self.stone_radius as i32, self.stone_radius as i32);
+            for (region, owner) in board.get_regions() {
+                for point in region {
+                    let corner = self.board_to_screen(point) - size / 2;
+                    match owner {
+                        Some(go::Player::Black) => {
+                            fb.fill_rect(corner, size.cast().unwrap(), color::BLACK);
+                        }
+                        Some(go::Player::White) => {
+                            fb.fill_rect(corner, size.cast().unwrap(), color::WHITE);
+                            fb.draw_rect(
+                                corner,
+                                size.cast().unwrap(),
+                                self.line_width,
+                                color::BLACK,
+                            );
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+
         let refresh_rect = mxcfb_rect {
             top: (self.board_start.y - self.square_size.y) as u32,
             left: (self.board_start.x - self.square_size.x) as u32,
@@ -237,3 +296,33 @@ impl UiComponent<Box<dyn GameController>> for BoardUi {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::point2;
+
+    #[test]
+    fn traditional_hoshi() {
+        assert_eq!(super::hoshi_points(19, 19).len(), 9);
+        assert!(super::hoshi_points(19, 19).contains(&point2(15, 9)));
+
+        let points = super::hoshi_points(13, 13);
+        assert_eq!(points.len(), 5);
+        assert!(points.contains(&point2(9, 3)));
+        assert!(points.contains(&point2(6, 6)));
+
+        let points = super::hoshi_points(9, 9);
+        assert_eq!(points.len(), 5);
+        assert!(points.contains(&point2(2, 6)));
+    }
+
+    #[test]
+    fn other_hoshi() {
+        assert_eq!(super::hoshi_points(5, 5), vec![point2(2, 2)]);
+        assert_eq!(super::hoshi_points(7, 7).len(), 5);
+        assert_eq!(super::hoshi_points(21, 21).len(), 9);
+        assert_eq!(super::hoshi_points(4, 4), vec![]);
+        assert_eq!(super::hoshi_points(10, 10).len(), 4);
+        assert_eq!(super::hoshi_points(9, 13).len(), 5);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::go::{other_player, splitmix64, BoardState, GamePhase, Player};
+use crate::settings::Difficulty;
+use cgmath::{point2, Point2};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// How strongly the search favours moves it knows little about over the ones winning most often
+const EXPLORATION: f32 = 1.0;
+
+// How many random points a playout tries before looking through every empty point for a move
+const GUESSES: usize = 8;
+
+// Playouts a move which captures a stone counts as having won before the search tries it, and
+// twice as many for more than one stone. The random playouts are as likely to lose a group which
+// could have been saved as one which couldn't, so on their own they hardly ever see a capture
+const CAPTURE_PRIOR: u32 = 15;
+
+// A move in the search, where None is a pass
+pub type Play = Option<Point2<usize>>;
+
+// A small pseudo random number generator, so a bot given the same seed always plays the same way
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(1);
+        return splitmix64(self.state);
+    }
+
+    fn below(&mut self, limit: usize) -> usize {
+        return (self.next() % limit as u64) as usize;
+    }
+}
+
+struct SearchNode {
+    played: Play,
+    // Who made the move leading here
+    player: Player,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // Moves from this position which haven't been added to the tree yet
+    untried: Vec<Play>,
+    visits: u32,
+    // Playouts won by the player who made the move, with a draw counting as half a win
+    wins: f32,
+}
+
+impl SearchNode {
+    fn win_rate(&self) -> f32 {
+        self.wins / self.visits.max(1) as f32
+    }
+}
+
+// Picks moves with a Monte Carlo tree search. Each playout finishes the game with random moves,
+// and the search spends more of its playouts on the moves which win most often
+pub struct Bot {
+    // Playouts per move, on top of the one each possible move gets to start with. Every move is
+    // tried however big the board is, and these are spent on the most promising of them
+    playouts: usize,
+    random: Random,
+}
+
+impl Bot {
+    // On an empty 19x19 board, a desktop takes about 0.27s to answer on Easy, 0.33s on Medium and
+    // 0.5s on Hard, and less as the board fills up. Most of Easy's time goes on trying every move
+    // once. These are yet to be measured on the reMarkable, which will be several times slower.
+    // The difficulty_timings test measures them
+    pub fn new(difficulty: Difficulty, seed: u64) -> Bot {
+        let playouts = match difficulty {
+            Difficulty::Easy => 50,
+            Difficulty::Medium => 150,
+            Difficulty::Hard => 400,
+        };
+        return Bot::with_playouts(playouts, seed);
+    }
+
+    pub fn with_playouts(playouts: usize, seed: u64) -> Bot {
+        Bot {
+            playouts,
+            random: Random { state: seed },
+        }
+    }
+
+    // The move for the player whose turn it is. The search gives up, returning None, as soon as it
+    // sees the cancelled flag raised
+    pub fn choose_move(&mut self, state: &BoardState, cancelled: &AtomicBool) -> Option<Play> {
+        let untried = root_moves(state);
+        let playouts = untried.len() + self.playouts;
+        let mut nodes = vec![SearchNode {
+            played: None,
+            player: other_player(state.current_player),
+            parent: None,
+            children: vec![],
+            untried,
+            visits: 0,
+            wins: 0.0,
+        }];
+
+        for _ in 0..playouts {
+            if cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+            let mut position = state.clone();
+            let mut node = 0;
+
+            // Follow the most promising moves until reaching one with moves left to try
+            while nodes[node].untried.is_empty() && !nodes[node].children.is_empty() {
+                node = best_child(&nodes, node);
+                play(&mut position, nodes[node].played);
+            }
+
+            if !nodes[node].untried.is_empty() {
+                let untried = &mut nodes[node].untried;
+                let played = untried.swap_remove(self.random.below(untried.len()));
+                let player = position.current_player;
+                let captured = captured_stones(&position);
+                play(&mut position, played);
+                let prior = match captured_stones(&position) - captured {
+                    0 => 0,
+                    1 => CAPTURE_PRIOR,
+                    _ => CAPTURE_PRIOR * 2,
+                };
+                nodes.push(SearchNode {
+                    played,
+                    player,
+                    parent: Some(node),
+                    children: vec![],
+                    untried: candidate_moves(&position),
+                    visits: prior,
+                    wins: prior as f32,
+                });
+                let child = nodes.len() - 1;
+                nodes[node].children.push(child);
+                node = child;
+            }
+
+            let winner = self.playout(&mut position);
+            let mut next = Some(node);
+            while let Some(index) = next {
+                let node = &mut nodes[index];
+                node.visits += 1;
+                node.wins += match winner {
+                    Some(player) if player == node.player => 1.0,
+                    Some(_) => 0.0,
+                    None => 0.5,
+                };
+                next = node.parent;
+            }
+        }
+
+        // The move tried most often, or of those, the one winning most often
+        let best = nodes[0]
+            .children
+            .iter()
+            .map(|child| &nodes[*child])
+            .max_by(|a, b| {
+                a.visits
+                    .cmp(&b.visits)
+                    .then(a.win_rate().total_cmp(&b.win_rate()))
+            });
+        return Some(best.and_then(|best| best.played));
+    }
+
+    // Plays random moves until both players pass, never filling a player's own eye, and returns
+    // the winner by area score, or None for a draw
+    fn playout(&mut self, position: &mut BoardState) -> Option<Player> {
+        let move_limit = position.width * position.height * 2;
+        let mut moves = 0;
+        while position.phase == GamePhase::Playing && moves < move_limit {
+            let played = self.random_move(position);
+            play(position, played);
+            moves += 1;
+        }
+
+        let score = position.area_score();
+        if score.black > score.white {
+            return Some(Player::Black);
+        } else if score.white > score.black {
+            return Some(Player::White);
+        }
+        return None;
+    }
+
+    fn random_move(&mut self, position: &BoardState) -> Play {
+        let player = position.current_player;
+        let playable = |point: Point2<usize>| {
+            position.stone_at(point).is_none()
+                && !is_own_eye(position, point, player)
+                && position.is_legal(point)
+        };
+
+        // Until the board fills up, a few random guesses find a move sooner than listing every
+        // empty point does
+        for _ in 0..GUESSES {
+            let point = point2(
+                self.random.below(position.width),
+                self.random.below(position.height),
+            );
+            if playable(point) {
+                return Some(point);
+            }
+        }
+
+        // Otherwise every point is looked at in turn, from a random one onwards
+        let (width, points) = (position.width, position.width * position.height);
+        let start = self.random.below(points);
+        return (start..start + points)
+            .map(|index| point2(index % points % width, index % points / width))
+            .find(|point| playable(*point));
+    }
+}
+
+// A point whose neighbours are all the player's own stones. Filling it could only help the
+// opponent, so neither the search nor the playouts consider it
+fn is_own_eye(state: &BoardState, point: Point2<usize>, player: Player) -> bool {
+    let neighbours = [
+        (point.x > 0).then(|| point2(point.x - 1, point.y)),
+        (point.x + 1 < state.width).then(|| point2(point.x + 1, point.y)),
+        (point.y > 0).then(|| point2(point.x, point.y - 1)),
+        (point.y + 1 < state.height).then(|| point2(point.x, point.y + 1)),
+    ];
+    return neighbours
+        .into_iter()
+        .flatten()
+        .all(|neighbour| state.stone_at(neighbour) == Some(player));
+}
+
+// Every legal move which doesn't fill an eye, and passing, or nothing once the game is over
+fn candidate_moves(state: &BoardState) -> Vec<Play> {
+    if state.phase != GamePhase::Playing {
+        return vec![];
+    }
+
+    let player = state.current_player;
+    let mut moves: Vec<Play> = state
+        .legal_moves()
+        .into_iter()
+        .filter(|point| !is_own_eye(state, *point, player))
+        .map(Some)
+        .collect();
+    moves.push(None);
+    return moves;
+}
+
+// The moves the search starts from. Passing is only one of them once the opponent has passed, or
+// when there is nothing else, so the computer doesn't pass while there are still points to play
+fn root_moves(state: &BoardState) -> Vec<Play> {
+    let mut moves = candidate_moves(state);
+    if moves.len() > 1 && !state.has_passed(other_player(state.current_player)) {
+        moves.retain(|played| played.is_some());
+    }
+    return moves;
+}
+
+fn captured_stones(state: &BoardState) -> u32 {
+    state.captures_made_by(Player::Black) + state.captures_made_by(Player::White)
+}
+
+fn play(state: &mut BoardState, played: Play) {
+    let result = match played {
+        Some(point) => state.try_play(point).is_ok(),
+        None => state.pass().is_ok(),
+    };
+    debug_assert!(result, "The search only plays legal moves");
+}
+
+// The child with the best upper confidence bound (UCT)
+fn best_child(nodes: &[SearchNode], node: usize) -> usize {
+    let log_visits = (nodes[node].visits.max(1) as f32).ln();
+    let bound = |child: usize| {
+        let child = &nodes[child];
+        let visits = child.visits.max(1) as f32;
+        child.win_rate() + EXPLORATION * (log_visits / visits).sqrt()
+    };
+
+    return *nodes[node]
+        .children
+        .iter()
+        .max_by(|a, b| bound(**a).total_cmp(&bound(**b)))
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bot;
+    use crate::go::{BoardState, Player};
+    use crate::settings::Difficulty;
+    use cgmath::point2;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Instant;
+
+    fn play_all(state: &mut BoardState, moves: &[(usize, usize)]) {
+        for (x, y) in moves {
+            state.try_play(point2(*x, *y)).unwrap();
+        }
+    }
+
+    #[test]
+    fn same_seed_same_move() {
+        let mut state = BoardState::new(9, 9);
+        play_all(&mut state, &[(2, 2), (6, 6), (2, 6)]);
+
+        let cancelled = AtomicBool::new(false);
+        let first = Bot::with_playouts(200, 7).choose_move(&state, &cancelled);
+        let second = Bot::with_playouts(200, 7).choose_move(&state, &cancelled);
+        assert!(matches!(first, Some(Some(_))));
+        assert_eq!(first, second);
+
+        let cancelled = AtomicBool::new(true);
+        assert_eq!(
+            Bot::with_playouts(200, 7).choose_move(&state, &cancelled),
+            None
+        );
+    }
+
+    // Not run with the others, as it takes a while: cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn difficulty_timings() {
+        let state = BoardState::new(19, 19);
+        let cancelled = AtomicBool::new(false);
+        for difficulty in Difficulty::ALL {
+            let start = Instant::now();
+            Bot::new(difficulty, 1).choose_move(&state, &cancelled);
+            println!("{} on 19x19: {:?}", difficulty.name(), start.elapsed());
+        }
+    }
+
+    #[test]
+    fn captures_stone_in_atari() {
+        // White's only stone has a single liberty left
+        let mut state = BoardState::new(5, 5);
+        for (x, y) in [(1, 2), (2, 1), (3, 2)] {
+            state.set_stone(point2(x, y), Some(Player::Black));
+        }
+        state.set_stone(point2(2, 2), Some(Player::White));
+        assert_eq!(state.current_player, Player::Black);
+
+        let mut bot = Bot::with_playouts(1000, 1);
+        let cancelled = AtomicBool::new(false);
+        assert_eq!(
+            bot.choose_move(&state, &cancelled),
+            Some(Some(point2(2, 3)))
+        );
+    }
+
+    #[test]
+    fn hard_captures_group_on_open_board() {
+        // Four white stones in the middle of a 13x13 board have a single liberty left
+        let mut state = BoardState::new(13, 13);
+        for (x, y) in [(5, 6), (5, 7), (6, 5), (7, 5), (8, 6), (8, 7), (6, 8)] {
+            state.set_stone(point2(x, y), Some(Player::Black));
+        }
+        for (x, y) in [(6, 6), (7, 6), (6, 7), (7, 7)] {
+            state.set_stone(point2(x, y), Some(Player::White));
+        }
+
+        let mut bot = Bot::new(Difficulty::Hard, 3);
+        let cancelled = AtomicBool::new(false);
+        assert_eq!(
+            bot.choose_move(&state, &cancelled),
+            Some(Some(point2(7, 8)))
+        );
+    }
+
+    #[test]
+    fn only_passes_after_opponent() {
+        let mut state = BoardState::new(9, 9);
+        assert!(!super::root_moves(&state).contains(&None));
+
+        state.pass().unwrap();
+        assert!(super::root_moves(&state).contains(&None));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::bot::{Bot, Play};
+use crate::go::BoardState;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+// Called from the worker's thread when a move is ready, to wake up whoever is waiting for it
+pub type Notify = Box<dyn Fn() + Send>;
+
+struct Request {
+    id: u64,
+    state: BoardState,
+    cancelled: Arc<AtomicBool>,
+}
+
+struct Reply {
+    id: u64,
+    played: Play,
+}
+
+// Runs the bot on a thread of its own, so the screen keeps responding while it thinks. Positions
+// are handed over one at a time, and the move comes back whenever the search finishes
+pub struct BotWorker {
+    requests: Sender<Request>,
+    replies: Receiver<Reply>,
+    // The position being thought about, and the flag which stops its search
+    current: Option<(u64, Arc<AtomicBool>)>,
+    next_id: u64,
+}
+
+impl BotWorker {
+    pub fn new(mut bot: Bot, notify: Notify) -> BotWorker {
+        let (requests, request_receiver) = channel::<Request>();
+        let (reply_sender, replies) = channel();
+        thread::spawn(move || {
+            for request in request_receiver {
+                let Some(played) = bot.choose_move(&request.state, &request.cancelled) else {
+                    continue;
+                };
+                if reply_sender
+                    .send(Reply {
+                        id: request.id,
+                        played,
+                    })
+                    .is_err()
+                {
+                    break;
+                }
+                notify();
+            }
+        });
+
+        BotWorker {
+            requests,
+            replies,
+            current: None,
+            next_id: 0,
+        }
+    }
+
+    // Starts thinking about a position, giving up on any other
+    pub fn think(&mut self, state: BoardState) {
+        self.cancel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.next_id += 1;
+        self.current = Some((self.next_id, cancelled.clone()));
+        let _ = self.requests.send(Request {
+            id: self.next_id,
+            state,
+            cancelled,
+        });
+    }
+
+    // Stops thinking. A move the search had already found is thrown away
+    pub fn cancel(&mut self) {
+        if let Some((_, cancelled)) = self.current.take() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.current.is_some()
+    }
+
+    // The move for the position being thought about, once the search has finished
+    pub fn take_move(&mut self) -> Option<Play> {
+        let id = self.current.as_ref()?.0;
+        while let Ok(reply) = self.replies.try_recv() {
+            if reply.id == id {
+                self.current = None;
+                return Some(reply.played);
+            }
+        }
+        return None;
+    }
+}
+
+// The thread finishes once it has nothing left to do and the worker is gone
+impl Drop for BotWorker {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BotWorker;
+    use crate::bot::Bot;
+    use crate::go::BoardState;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn replies_to_latest_position() {
+        let (sender, receiver) = channel();
+        let notify = Box::new(move || {
+            let _ = sender.send(());
+        });
+        let mut worker = BotWorker::new(Bot::with_playouts(20, 1), notify);
+        assert_eq!(worker.take_move(), None);
+
+        // Only the last position handed over is answered
+        worker.think(BoardState::new(9, 9));
+        worker.think(BoardState::new(5, 5));
+        assert!(worker.is_thinking());
+        let mut played = None;
+        while played.is_none() {
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+            played = worker.take_move();
+        }
+
+        let point = played.unwrap().unwrap();
+        assert!(point.x < 5 && point.y < 5);
+        assert!(!worker.is_thinking());
+
+        worker.think(BoardState::new(5, 5));
+        worker.cancel();
+        assert!(!worker.is_thinking());
+        assert_eq!(worker.take_move(), None);
+    }
+}
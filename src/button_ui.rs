@@ -0,0 +1,112 @@
+use crate::text::TextAlignment;
+use crate::ui::{UiComponent, UiController, UiEvent};
+use crate::{drawing, text, ui};
+use cgmath::{vec2, Point2, Vector2};
+use libremarkable::framebuffer::common::{
+    color, display_temp, dither_mode, mxcfb_rect, waveform_mode, DRAWING_QUANT_BIT,
+};
+use libremarkable::framebuffer::{FramebufferDraw, FramebufferRefresh, PartialRefreshMode};
+use libremarkable::input::{InputEvent, MultitouchEvent};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::string::String;
+
+pub struct ButtonUi<State> {
+    label: String,
+    position: Point2<i32>,
+    size: Vector2<u32>,
+    text_size: i32,
+    callback: Box<dyn Fn(Rc<RefCell<&mut UiController>>, &mut State)>,
+    // The button is hidden, and ignores presses, when this returns false
+    visible: Box<dyn Fn(&State) -> bool>,
+}
+
+impl<State> ButtonUi<State> {
+    pub fn new(
+        position: Point2<i32>,
+        size: Vector2<u32>,
+        label: &str,
+        callback: Box<dyn Fn(Rc<RefCell<&mut UiController>>, &mut State)>,
+    ) -> ButtonUi<State> {
+        ButtonUi {
+            label: String::from(label),
+            position,
+            size,
+            text_size: 18,
+            callback,
+            visible: Box::new(|_| true),
+        }
+    }
+
+    pub fn visible_when(mut self, visible: Box<dyn Fn(&State) -> bool>) -> ButtonUi<State> {
+        self.visible = visible;
+        self
+    }
+}
+
+impl<State> UiComponent<State> for ButtonUi<State> {
+    fn handle_event(
+        &mut self,
+        ui: Rc<RefCell<&mut UiController>>,
+        state: &mut State,
+        event: &UiEvent,
+    ) {
+        if !(self.visible)(state) {
+            return;
+        }
+
+        if let UiEvent::Input(InputEvent::MultitouchEvent { event, .. }) = event {
+            if let MultitouchEvent::Press { finger } = event {
+                let end = self.position + self.size.cast().unwrap();
+                if finger.pos.x >= self.position.x as u16
+                    && finger.pos.x < end.x as u16
+                    && finger.pos.y >= self.position.y as u16
+                    && finger.pos.y < end.y as u16
+                {
+                    (self.callback)(ui.clone(), state);
+                    ui::mark_handled();
+                    ui::post_redraw();
+                }
+            }
+        }
+    }
+
+    fn draw(&self, ui: Rc<RefCell<&mut UiController>>, state: &State) {
+        let fb = ui.borrow_mut().context.get_framebuffer_ref();
+
+        fb.fill_rect(self.position, self.size, color::WHITE);
+        if (self.visible)(state) {
+            let text_offset = vec2(
+                self.size.x as i32 / 2,
+                (self.size.y as i32 - self.text_size) / 2,
+            );
+
+            drawing::draw_rect(fb, self.position, self.size, 2);
+            text::draw_text(
+                fb,
+                self.position + text_offset,
+                TextAlignment::Centered,
+                self.text_size,
+                color::BLACK,
+                &self.label,
+            );
+        }
+
+        let refresh_rect = mxcfb_rect {
+            top: self.position.y as u32,
+            left: self.position.x as u32,
+            width: self.size.x,
+            height: self.size.y,
+        };
+
+        fb.partial_refresh(
+            &refresh_rect,
+            PartialRefreshMode::Async,
+            waveform_mode::WAVEFORM_MODE_GC16_FAST,
+            display_temp::TEMP_USE_REMARKABLE_DRAW,
+            dither_mode::EPDC_FLAG_USE_DITHERING_PASSTHROUGH,
+            DRAWING_QUANT_BIT,
+            false,
+        );
+    }
+}
@@ -0,0 +1,613 @@
+use crate::game_tree::{GameTree, Node};
+use crate::go::{other_player, BoardState, GamePhase, Player};
+use crate::settings::{Clock, GameSettings, Mode};
+use std::time::{Duration, Instant};
+
+// Where the clock reads the time from, so tests can move it by hand
+pub trait TimeSource {
+    // The time since some fixed point, which never goes backwards
+    fn now(&self) -> Duration;
+}
+
+pub struct SystemTime {
+    start: Instant,
+}
+
+impl SystemTime {
+    pub fn new() -> SystemTime {
+        SystemTime {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl TimeSource for SystemTime {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+// How much time each player gets. Every kind starts with main time, and differs in what happens
+// once it runs out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeControl {
+    // The game is lost when the main time runs out
+    Absolute {
+        main: Duration,
+    },
+    // Each move adds the increment to the main time
+    Fischer {
+        main: Duration,
+        increment: Duration,
+    },
+    // Japanese byo-yomi. A move made within a period leaves it to be used again, and a period which
+    // runs out in full is gone
+    ByoYomi {
+        main: Duration,
+        periods: u32,
+        period: Duration,
+    },
+    // Each block of overtime has to cover the given number of stones, then a new block starts
+    Canadian {
+        main: Duration,
+        stones: u32,
+        period: Duration,
+    },
+}
+
+impl TimeControl {
+    // The time control the menu's clock setting stands for, if there is one
+    pub fn preset(clock: Clock) -> Option<TimeControl> {
+        match clock {
+            Clock::Blitz => Some(TimeControl::Fischer {
+                main: Duration::from_secs(5 * 60),
+                increment: Duration::from_secs(3),
+            }),
+            Clock::Rapid => Some(TimeControl::ByoYomi {
+                main: Duration::from_secs(20 * 60),
+                periods: 5,
+                period: Duration::from_secs(30),
+            }),
+            Clock::Canadian => Some(TimeControl::Canadian {
+                main: Duration::from_secs(10 * 60),
+                stones: 10,
+                period: Duration::from_secs(5 * 60),
+            }),
+            Clock::Absolute => Some(TimeControl::Absolute {
+                main: Duration::from_secs(30 * 60),
+            }),
+            Clock::Untimed => None,
+        }
+    }
+
+    fn initial_time(&self) -> PlayerTime {
+        let (main, overtime, periods) = match *self {
+            TimeControl::Absolute { main } | TimeControl::Fischer { main, .. } => {
+                (main, Duration::ZERO, 0)
+            }
+            TimeControl::ByoYomi {
+                main,
+                periods,
+                period,
+            } => (main, period, periods),
+            TimeControl::Canadian {
+                main,
+                stones,
+                period,
+            } => (main, period, stones),
+        };
+        PlayerTime {
+            main,
+            overtime,
+            periods,
+            out_of_time: false,
+        }
+    }
+
+    // Takes time spent thinking off what a player has left
+    fn spend(&self, mut time: PlayerTime, elapsed: Duration) -> PlayerTime {
+        if elapsed < time.main {
+            time.main -= elapsed;
+            return time;
+        }
+        let mut elapsed = elapsed - time.main;
+        time.main = Duration::ZERO;
+
+        match self {
+            TimeControl::Absolute { .. } | TimeControl::Fischer { .. } => time.out_of_time = true,
+            TimeControl::ByoYomi { period, .. } => {
+                while elapsed >= time.overtime && !time.out_of_time {
+                    elapsed -= time.overtime;
+                    time.periods -= 1;
+                    time.overtime = *period;
+                    time.out_of_time = time.periods == 0;
+                }
+                if time.out_of_time {
+                    time.overtime = Duration::ZERO;
+                } else {
+                    time.overtime -= elapsed;
+                }
+            }
+            TimeControl::Canadian { .. } => {
+                time.overtime = time.overtime.saturating_sub(elapsed);
+                time.out_of_time = time.overtime == Duration::ZERO;
+            }
+        }
+        return time;
+    }
+
+    // Gives a player whatever a move in time earns them
+    fn finish_move(&self, mut time: PlayerTime) -> PlayerTime {
+        match *self {
+            TimeControl::Absolute { .. } => {}
+            TimeControl::Fischer { increment, .. } => time.main += increment,
+            TimeControl::ByoYomi { period, .. } if time.main.is_zero() => time.overtime = period,
+            TimeControl::Canadian { stones, period, .. } if time.main.is_zero() => {
+                time.periods -= 1;
+                if time.periods == 0 {
+                    time.periods = stones;
+                    time.overtime = period;
+                }
+            }
+            TimeControl::ByoYomi { .. } | TimeControl::Canadian { .. } => {}
+        }
+        return time;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerTime {
+    pub main: Duration,
+    // What is left of the current byo-yomi period or Canadian block
+    pub overtime: Duration,
+    // Byo-yomi periods left, counting the current one, or stones left to play in the Canadian
+    // block
+    pub periods: u32,
+    pub out_of_time: bool,
+}
+
+// Both players' clocks, with at most one of them running
+pub struct GameClock {
+    control: TimeControl,
+    // Each player's time as it was when their clock last started
+    black: PlayerTime,
+    white: PlayerTime,
+    running: Option<Player>,
+    started: Duration,
+    source: Box<dyn TimeSource>,
+}
+
+impl GameClock {
+    pub fn new(control: TimeControl, source: Box<dyn TimeSource>) -> GameClock {
+        GameClock {
+            control,
+            black: control.initial_time(),
+            white: control.initial_time(),
+            running: None,
+            started: source.now(),
+            source,
+        }
+    }
+
+    pub fn running(&self) -> Option<Player> {
+        self.running
+    }
+
+    fn stored_time(&mut self, player: Player) -> &mut PlayerTime {
+        match player {
+            Player::Black => &mut self.black,
+            Player::White => &mut self.white,
+        }
+    }
+
+    pub fn time_left(&self, player: Player) -> PlayerTime {
+        let time = match player {
+            Player::Black => self.black,
+            Player::White => self.white,
+        };
+        if self.running != Some(player) {
+            return time;
+        }
+        return self.control.spend(time, self.source.now() - self.started);
+    }
+
+    // The player whose time has run out, if either has
+    pub fn out_of_time(&self) -> Option<Player> {
+        [Player::Black, Player::White]
+            .into_iter()
+            .find(|player| self.time_left(*player).out_of_time)
+    }
+
+    // How a player's clock reads. Overtime which hasn't started is shown after the main time, and
+    // once it has, the periods or stones left are shown after the time
+    pub fn display(&self, player: Player) -> String {
+        let time = self.time_left(player);
+        if time.main.is_zero() && !time.overtime.is_zero() {
+            let (count, one, many) = match self.control {
+                TimeControl::Canadian { .. } => (time.periods, "stone", "stones"),
+                _ => (time.periods, "period", "periods"),
+            };
+            let unit = if count == 1 { one } else { many };
+            return format!("{}  {} {}", clock_text(time.overtime), count, unit);
+        }
+
+        let main = clock_text(time.main);
+        match self.control {
+            TimeControl::Absolute { .. } | TimeControl::Fischer { .. } => main,
+            TimeControl::ByoYomi {
+                periods, period, ..
+            } => format!("{} + {}x{}", main, periods, clock_text(period)),
+            TimeControl::Canadian { stones, period, .. } => {
+                format!("{} + {}/{}", main, stones, clock_text(period))
+            }
+        }
+    }
+
+    // Whether a player has ten seconds or less before their main time or current period runs out
+    pub fn running_low(&self, player: Player) -> bool {
+        let time = self.time_left(player);
+        let countdown = match time.main.is_zero() {
+            true => time.overtime,
+            false => time.main,
+        };
+        return countdown <= Duration::from_secs(10);
+    }
+
+    // Stops the running clock, keeping the time spent so far, and starts the given player's. A
+    // player whose clock is stopped part way through a turn carries on from the same point
+    pub fn run(&mut self, player: Option<Player>) {
+        if let Some(running) = self.running {
+            *self.stored_time(running) = self.time_left(running);
+        }
+        self.started = self.source.now();
+        self.running = player;
+    }
+
+    // The running player has moved, so they get any time the move earns and their opponent's
+    // clock starts
+    pub fn moved(&mut self) {
+        if let Some(player) = self.running {
+            self.run(Some(other_player(player)));
+            let time = *self.stored_time(player);
+            if !time.out_of_time {
+                *self.stored_time(player) = self.control.finish_move(time);
+            }
+        }
+    }
+
+    // Keeps the clock in step with the game. Only the player to move has their clock running, and
+    // only while the game is being played
+    pub fn follow(&mut self, state: &BoardState) {
+        let player = match state.phase {
+            GamePhase::Playing => Some(state.current_player),
+            _ => None,
+        };
+        if self.running != player {
+            self.run(player);
+        }
+    }
+
+    // After a move has been added to the tree, ends the mover's turn, notes how much time they have
+    // left on the move's node and starts the next player's clock
+    pub fn after_move(&mut self, tree: &mut GameTree) {
+        let player = self.running;
+        self.moved();
+        if let Some(player) = player {
+            let current = tree.current();
+            self.record(player, tree.node_mut(current));
+        }
+        self.follow(tree.current_state());
+    }
+
+    // Writes a player's time left the way SGF does, as seconds in BL or WL. Once they are in
+    // overtime, OB or OW says how many periods or stones they have left
+    fn record(&self, player: Player, node: &mut Node) {
+        let time = self.time_left(player);
+        let (left, periods) = property_names(player);
+        if !time.main.is_zero() || time.overtime.is_zero() {
+            node.set_property(left, &format!("{:.1}", time.main.as_secs_f64()));
+            node.properties.retain(|(name, _)| name != periods);
+        } else {
+            node.set_property(left, &format!("{:.1}", time.overtime.as_secs_f64()));
+            node.set_property(periods, &time.periods.to_string());
+        }
+    }
+
+    // Sets each player's time from the last record of it before the cursor, so a game carries on
+    // with the clocks it was saved with. The clocks are left stopped
+    pub fn restore(&mut self, tree: &GameTree) {
+        self.run(None);
+        for player in [Player::Black, Player::White] {
+            let (left, periods) = property_names(player);
+            let node = tree
+                .path()
+                .map(|index| tree.node(index))
+                .filter(|node| node.property(left).is_some())
+                .last();
+            let seconds = node
+                .and_then(|node| node.property(left))
+                .and_then(|value| value.parse::<f64>().ok())
+                .filter(|seconds| seconds.is_finite() && *seconds >= 0.0);
+            let Some(seconds) = seconds else {
+                continue;
+            };
+
+            let mut time = self.control.initial_time();
+            let left = Duration::from_secs_f64(seconds);
+            match node
+                .and_then(|node| node.property(periods))
+                .and_then(|value| value.parse::<u32>().ok())
+            {
+                Some(periods) if periods > 0 => {
+                    time.main = Duration::ZERO;
+                    time.overtime = left;
+                    time.periods = periods;
+                }
+                _ => time.main = left,
+            }
+            *self.stored_time(player) = time;
+        }
+    }
+}
+
+// The clock for a game with the given settings, carrying on from any times its record has, and
+// running for whoever is to move
+pub fn start(settings: &GameSettings, tree: &GameTree) -> Option<GameClock> {
+    // The computer doesn't play against the clock
+    if settings.mode == Mode::OnePlayer {
+        return None;
+    }
+    let control = TimeControl::preset(settings.clock)?;
+    let mut clock = GameClock::new(control, Box::new(SystemTime::new()));
+    clock.restore(tree);
+    clock.follow(tree.current_state());
+    return Some(clock);
+}
+
+// Keeps the clock in step after a change to the game which isn't a move. Like the two below, it
+// does nothing in an untimed game, which has no clock
+pub fn follow(clock: &mut Option<GameClock>, tree: &GameTree) {
+    if let Some(clock) = clock {
+        clock.follow(tree.current_state());
+    }
+}
+
+// Ends the mover's turn on the clock
+pub fn after_move(clock: &mut Option<GameClock>, tree: &mut GameTree) {
+    if let Some(clock) = clock {
+        clock.after_move(tree);
+    }
+}
+
+// Ends the game if the player to move has run out of time. Returns whether it did, so the game can
+// be saved
+pub fn check_time(clock: &mut Option<GameClock>, tree: &mut GameTree) -> bool {
+    let Some(clock) = clock else {
+        return false;
+    };
+    let Some(player) = clock.out_of_time() else {
+        return false;
+    };
+    if tree.current_state_mut().time_out(player).is_err() {
+        return false;
+    }
+    clock.follow(tree.current_state());
+    return true;
+}
+
+// Minutes and seconds, with hours if there are any. Part of a second counts as a whole one, so the
+// clock only reads 0:00 once the time has run out
+fn clock_text(time: Duration) -> String {
+    let seconds = time.as_millis().div_ceil(1000);
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+fn property_names(player: Player) -> (&'static str, &'static str) {
+    match player {
+        Player::Black => ("BL", "OB"),
+        Player::White => ("WL", "OW"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameClock, TimeControl, TimeSource};
+    use crate::game_tree::GameTree;
+    use crate::go::{BoardState, GamePhase, Player};
+    use cgmath::point2;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    struct FakeTime(Rc<Cell<Duration>>);
+
+    impl TimeSource for FakeTime {
+        fn now(&self) -> Duration {
+            self.0.get()
+        }
+    }
+
+    fn fake_clock(control: TimeControl) -> (GameClock, impl Fn(u64)) {
+        let now = Rc::new(Cell::new(Duration::ZERO));
+        let clock = GameClock::new(control, Box::new(FakeTime(now.clone())));
+        let wait = move |seconds| now.set(now.get() + Duration::from_secs(seconds));
+        (clock, wait)
+    }
+
+    fn seconds(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn absolute() {
+        let (mut clock, wait) = fake_clock(TimeControl::Absolute { main: seconds(60) });
+        clock.run(Some(Player::Black));
+        wait(20);
+        clock.moved();
+        assert_eq!(clock.running(), Some(Player::White));
+        wait(30);
+        assert_eq!(clock.time_left(Player::Black).main, seconds(40));
+        assert_eq!(clock.time_left(Player::White).main, seconds(30));
+
+        // A stopped clock doesn't run down
+        clock.run(None);
+        wait(100);
+        assert_eq!(clock.out_of_time(), None);
+        clock.run(Some(Player::White));
+        wait(30);
+        assert_eq!(clock.out_of_time(), Some(Player::White));
+    }
+
+    #[test]
+    fn fischer() {
+        let (mut clock, wait) = fake_clock(TimeControl::Fischer {
+            main: seconds(10),
+            increment: seconds(5),
+        });
+        clock.run(Some(Player::Black));
+        wait(8);
+        clock.moved();
+        assert_eq!(clock.time_left(Player::Black).main, seconds(7));
+        wait(9);
+        clock.moved();
+        assert_eq!(clock.time_left(Player::White).main, seconds(6));
+        wait(7);
+        assert_eq!(clock.out_of_time(), Some(Player::Black));
+    }
+
+    #[test]
+    fn byo_yomi() {
+        let (mut clock, wait) = fake_clock(TimeControl::ByoYomi {
+            main: seconds(10),
+            periods: 3,
+            period: seconds(5),
+        });
+        clock.run(Some(Player::Black));
+        wait(13);
+        let time = clock.time_left(Player::Black);
+        assert_eq!(
+            (time.main, time.overtime, time.periods),
+            (seconds(0), seconds(2), 3)
+        );
+
+        // Moving within the period keeps it
+        clock.moved();
+        clock.moved();
+        assert_eq!(clock.time_left(Player::Black).overtime, seconds(5));
+
+        // Running over uses up periods
+        wait(11);
+        let time = clock.time_left(Player::Black);
+        assert_eq!((time.overtime, time.periods), (seconds(4), 1));
+        clock.moved();
+        clock.moved();
+        wait(5);
+        assert_eq!(clock.out_of_time(), Some(Player::Black));
+    }
+
+    #[test]
+    fn canadian() {
+        let (mut clock, wait) = fake_clock(TimeControl::Canadian {
+            main: seconds(0),
+            stones: 2,
+            period: seconds(10),
+        });
+        clock.run(Some(Player::Black));
+        wait(4);
+        clock.moved();
+        clock.moved();
+        let time = clock.time_left(Player::Black);
+        assert_eq!((time.overtime, time.periods), (seconds(6), 1));
+
+        // The block's last stone starts a new block
+        wait(5);
+        clock.moved();
+        let time = clock.time_left(Player::Black);
+        assert_eq!((time.overtime, time.periods), (seconds(10), 2));
+
+        clock.moved();
+        wait(4);
+        clock.moved();
+        clock.moved();
+        wait(6);
+        assert_eq!(clock.out_of_time(), Some(Player::Black));
+    }
+
+    #[test]
+    fn display() {
+        let (mut clock, wait) = fake_clock(TimeControl::ByoYomi {
+            main: seconds(3600),
+            periods: 3,
+            period: seconds(30),
+        });
+        assert_eq!(clock.display(Player::Black), "1:00:00 + 3x0:30");
+        clock.run(Some(Player::Black));
+        wait(3600 - 75);
+        assert_eq!(clock.display(Player::Black), "1:15 + 3x0:30");
+        assert!(!clock.running_low(Player::Black));
+        wait(100);
+        assert_eq!(clock.display(Player::Black), "0:05  3 periods");
+        assert!(clock.running_low(Player::Black));
+
+        let (mut clock, wait) = fake_clock(TimeControl::Canadian {
+            main: seconds(0),
+            stones: 1,
+            period: seconds(300),
+        });
+        clock.run(Some(Player::White));
+        wait(1);
+        assert_eq!(clock.display(Player::White), "4:59  1 stone");
+    }
+
+    #[test]
+    fn recorded_in_tree() {
+        let control = TimeControl::ByoYomi {
+            main: seconds(10),
+            periods: 3,
+            period: seconds(5),
+        };
+        let (mut clock, wait) = fake_clock(control);
+        let mut tree = GameTree::new(BoardState::new(9, 9));
+        clock.follow(tree.current_state());
+        wait(4);
+        tree.play(point2(2, 2)).unwrap();
+        clock.after_move(&mut tree);
+        wait(12);
+        tree.play(point2(6, 6)).unwrap();
+        clock.after_move(&mut tree);
+        assert_eq!(tree.current_node().property("WL"), Some("5.0"));
+        assert_eq!(tree.current_node().property("OW"), Some("3"));
+
+        let (mut resumed, _) = fake_clock(control);
+        resumed.restore(&tree);
+        assert_eq!(
+            resumed.time_left(Player::Black),
+            clock.time_left(Player::Black)
+        );
+        assert_eq!(
+            resumed.time_left(Player::White),
+            clock.time_left(Player::White)
+        );
+        assert_eq!(resumed.running(), None);
+    }
+
+    #[test]
+    fn time_out() {
+        let (clock, wait) = fake_clock(TimeControl::Absolute { main: seconds(60) });
+        let mut clock = Some(clock);
+        let mut tree = GameTree::new(BoardState::new(9, 9));
+        super::follow(&mut clock, &tree);
+        wait(30);
+        assert!(!super::check_time(&mut clock, &mut tree));
+
+        wait(30);
+        assert!(super::check_time(&mut clock, &mut tree));
+        assert_eq!(tree.current_state().phase, GamePhase::Finished);
+        assert_eq!(clock.as_ref().unwrap().running(), None);
+        assert!(!super::check_time(&mut clock, &mut tree));
+        assert!(!super::check_time(&mut None, &mut tree));
+    }
+}
@@ -1,5 +1,8 @@
-use crate::go::BoardState;
+use crate::clock::GameClock;
+use crate::game_tree::GameTree;
+use crate::go::{BoardState, GamePhase, MoveError, Player};
 use cgmath::Point2;
+use std::io;
 
 pub struct ControllerOption {
     pub(crate) name: String,
@@ -7,8 +10,52 @@ pub struct ControllerOption {
 }
 
 pub trait GameController {
+    fn game_tree(&self) -> &GameTree;
     fn current_game_state(&self) -> &BoardState;
-    fn try_play(&mut self, point: Point2<usize>) -> Result<(), &str>;
+    fn try_play(&mut self, point: Point2<usize>) -> Result<(), MoveError>;
+    // Why the last move or pass was refused, until one is made
+    fn move_error(&self) -> Option<&MoveError>;
+    // Why the game couldn't be saved, until it next saves successfully
+    fn save_error(&self) -> Option<&io::Error> {
+        None
+    }
+    fn pass(&mut self) -> Result<(), MoveError>;
+    fn resign(&mut self) -> Result<(), &str>;
+    fn toggle_handicap_stone(&mut self, point: Point2<usize>) -> Result<(), &str>;
+    fn confirm_handicap(&mut self) -> Result<(), &str>;
+    fn toggle_dead(&mut self, point: Point2<usize>) -> Result<(), &str>;
+    fn accept_score(&mut self, player: Player) -> Result<(), &str>;
+    // Takes back the last move, or as many as the mode takes back at once, so they can be redone
+    fn undo(&mut self) -> Result<(), &str>;
+    fn redo(&mut self) -> Result<(), &str>;
+
+    fn game_phase(&self) -> GamePhase {
+        self.current_game_state().phase
+    }
+
+    // Both players' clocks, in games which are timed
+    fn clock(&self) -> Option<&GameClock> {
+        None
+    }
+
+    // Ends the game if the player to move has run out of time
+    fn check_time(&mut self) {}
+
+    // The player the computer is choosing a move for, while it thinks
+    fn thinking(&self) -> Option<Player> {
+        None
+    }
+
+    // Picks up anything finished in the background, such as the computer's move. Returns whether
+    // the game changed
+    fn poll(&mut self) -> bool {
+        false
+    }
+
+    // A request to the other player which they haven't answered yet
+    fn pending_request(&self) -> Option<&str> {
+        None
+    }
 }
 
 impl ControllerOption {
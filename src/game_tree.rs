@@ -0,0 +1,550 @@
+use crate::go::{BoardState, GamePhase, MoveError, Player};
+use crate::sgf;
+use cgmath::{point2, Point2};
+
+// Private SGF properties on the node the game is scored from. Marking dead stones and accepting
+// the count aren't moves, so they're kept here to be replayed along with the node
+const DEAD_PROPERTY: &str = "XDEAD";
+const ACCEPTED_PROPERTY: &str = "XACCEPTED";
+
+// A stone played by one of the players, or a pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Play(Player, Point2<usize>),
+    Pass(Player),
+}
+
+// One position in a game, and how it was reached from its parent
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Node {
+    // Stones added to or cleared from the board before the move, as SGF's AB, AW and AE do
+    pub setup: Vec<(Point2<usize>, Option<Player>)>,
+    pub played: Option<Move>,
+    pub comment: String,
+    // Any other properties, in the order they were read, so they can be written back unchanged
+    pub properties: Vec<(String, Vec<String>)>,
+    pub parent: Option<usize>,
+    // The first child continues the main line, and any others are variations
+    pub children: Vec<usize>,
+}
+
+impl Node {
+    pub fn new(played: Option<Move>) -> Node {
+        Node {
+            played,
+            ..Node::default()
+        }
+    }
+
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(key, _)| key == name)
+            .and_then(|(_, values)| values.first())
+            .map(|value| value.as_str())
+    }
+
+    pub fn set_property(&mut self, name: &str, value: &str) {
+        self.set_values(name, vec![value.to_string()]);
+    }
+
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        self.properties
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, values)| values.iter().map(|value| value.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    // Replaces a property's values, leaving it out of the record when there are none
+    pub fn set_values(&mut self, name: &str, values: Vec<String>) {
+        let existing = self.properties.iter().position(|(key, _)| key == name);
+        match (existing, values.is_empty()) {
+            (Some(index), true) => {
+                self.properties.remove(index);
+            }
+            (Some(index), false) => self.properties[index].1 = values,
+            (None, true) => {}
+            (None, false) => self.properties.push((name.to_string(), values)),
+        }
+    }
+}
+
+// Every move of a game, including variations, with a cursor on the position being shown. Nodes
+// are referred to by their index, and the root is always 0
+pub struct GameTree {
+    nodes: Vec<Node>,
+    // The nodes from the root to the cursor, along with the position at each of them. The root's
+    // position is never rebuilt, and its stones are kept in the root's setup
+    path: Vec<(usize, BoardState)>,
+    // The nodes undo has stepped back from, most recent last, which redo steps forward onto again
+    undone: Vec<usize>,
+}
+
+impl GameTree {
+    pub fn new(root_state: BoardState) -> GameTree {
+        let mut tree = GameTree {
+            nodes: vec![Node::new(None)],
+            path: vec![(0, root_state)],
+            undone: vec![],
+        };
+        tree.record_root_stones();
+        return tree;
+    }
+
+    pub fn node(&self, index: usize) -> &Node {
+        &self.nodes[index]
+    }
+
+    pub fn node_mut(&mut self, index: usize) -> &mut Node {
+        &mut self.nodes[index]
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.nodes[0]
+    }
+
+    pub fn root_mut(&mut self) -> &mut Node {
+        &mut self.nodes[0]
+    }
+
+    pub fn root_state(&self) -> &BoardState {
+        &self.path[0].1
+    }
+
+    // The node the cursor is on
+    pub fn current(&self) -> usize {
+        self.path.last().unwrap().0
+    }
+
+    pub fn current_node(&self) -> &Node {
+        &self.nodes[self.current()]
+    }
+
+    pub fn current_state(&self) -> &BoardState {
+        &self.path.last().unwrap().1
+    }
+
+    // Changes which end the game, such as resigning, are made to the current position directly.
+    // They only last until the cursor moves off the node, which a finished game doesn't do
+    pub fn current_state_mut(&mut self) -> &mut BoardState {
+        &mut self.path.last_mut().unwrap().1
+    }
+
+    // Places or removes one of black's handicap stones, which go on the root before any moves
+    pub fn toggle_handicap_stone(&mut self, point: Point2<usize>) -> Result<(), &'static str> {
+        if self.path.len() > 1 {
+            return Err("Handicap stones can only be placed before the game");
+        }
+        self.path[0].1.toggle_handicap_stone(point)?;
+        self.record_root_stones();
+        return Ok(());
+    }
+
+    fn record_root_stones(&mut self) {
+        let state = &self.path[0].1;
+        let mut stones = vec![];
+        for y in 0..state.height {
+            for x in 0..state.width {
+                if let Some(player) = state.stone_at(point2(x, y)) {
+                    stones.push((point2(x, y), Some(player)));
+                }
+            }
+        }
+        self.nodes[0].setup = stones;
+    }
+
+    pub fn toggle_dead(&mut self, point: Point2<usize>) -> Result<(), &'static str> {
+        self.current_state_mut().toggle_dead(point)?;
+        let state = self.current_state();
+        let dead = state
+            .dead_stones
+            .iter()
+            .map(|point| sgf::point_to_sgf(*point));
+        let mut dead: Vec<String> = dead.collect();
+        dead.sort();
+
+        // Changing the dead stones means both players have to accept the count again
+        let node = self.current_node_mut();
+        node.set_values(DEAD_PROPERTY, dead);
+        node.set_values(ACCEPTED_PROPERTY, vec![]);
+        return Ok(());
+    }
+
+    pub fn accept_score(&mut self, player: Player) -> Result<(), &'static str> {
+        self.current_state_mut().accept_score(player)?;
+        let node = self.current_node_mut();
+        let mut accepted: Vec<String> = node
+            .values(ACCEPTED_PROPERTY)
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        accepted.push(sgf::player_to_sgf(player).to_string());
+        accepted.sort();
+        accepted.dedup();
+        node.set_values(ACCEPTED_PROPERTY, accepted);
+        return Ok(());
+    }
+
+    fn current_node_mut(&mut self) -> &mut Node {
+        let current = self.current();
+        &mut self.nodes[current]
+    }
+
+    // The nodes from the root to the cursor
+    pub fn path(&self) -> impl Iterator<Item = usize> + '_ {
+        self.path.iter().map(|(index, _)| *index)
+    }
+
+    // How many moves, including passes, lead to the current position
+    pub fn move_number(&self) -> usize {
+        self.path()
+            .filter(|index| self.nodes[*index].played.is_some())
+            .count()
+    }
+
+    pub fn play(&mut self, point: Point2<usize>) -> Result<(), MoveError> {
+        let player = self.current_state().current_player;
+        self.add_child(Node::new(Some(Move::Play(player, point))))
+    }
+
+    pub fn pass(&mut self) -> Result<(), MoveError> {
+        let player = self.current_state().current_player;
+        let mut state = self.current_state().clone();
+        state.pass()?;
+        self.push_child(Node::new(Some(Move::Pass(player))), state);
+        return Ok(());
+    }
+
+    // Adds a node after the current one and moves the cursor to it. Playing a move which is
+    // already in the tree follows the existing node instead of adding a variation
+    pub fn add_child(&mut self, node: Node) -> Result<(), MoveError> {
+        let mut state = self.current_state().clone();
+        apply(&mut state, &node)?;
+        self.push_child(node, state);
+        return Ok(());
+    }
+
+    fn push_child(&mut self, node: Node, state: BoardState) {
+        let parent = self.current();
+        // Only a bare move is merged, so nodes read from a file keep their comments and properties
+        let bare_move = node == Node::new(node.played);
+        let existing = self.nodes[parent].children.iter().find(|child| {
+            let child = &self.nodes[**child];
+            bare_move && child.played == node.played && child.setup.is_empty()
+        });
+
+        let index = match existing {
+            Some(index) => *index,
+            None => {
+                self.nodes.push(Node {
+                    parent: Some(parent),
+                    children: vec![],
+                    ..node
+                });
+                let index = self.nodes.len() - 1;
+                self.nodes[parent].children.push(index);
+                index
+            }
+        };
+        self.path.push((index, state));
+
+        // Playing the move which was undone is the same as redoing it, anything else starts afresh
+        match self.undone.last() {
+            Some(undone) if *undone == index => {
+                self.undone.pop();
+            }
+            _ => self.undone.clear(),
+        }
+    }
+
+    // Moves the cursor back one move. Returns false at the root
+    pub fn go_to_parent(&mut self) -> bool {
+        if self.path.len() == 1 {
+            return false;
+        }
+
+        self.path.pop();
+        return true;
+    }
+
+    // Steps back one move, remembering it for redo. Returns false at the root
+    pub fn undo(&mut self) -> bool {
+        let current = self.current();
+        if !self.go_to_parent() {
+            return false;
+        }
+
+        self.undone.push(current);
+        return true;
+    }
+
+    // Steps forward onto the move which was last undone, or otherwise along the main line. Returns
+    // false when there is no move to step onto
+    pub fn redo(&mut self) -> bool {
+        let current = self.current();
+        let next = match self.undone.pop() {
+            Some(node) if self.nodes[node].parent == Some(current) => Some(node),
+            _ => {
+                self.undone.clear();
+                self.current_node().children.first().cloned()
+            }
+        };
+
+        match next {
+            Some(node) => self.go_to(node).is_ok(),
+            None => false,
+        }
+    }
+
+    // Moves the cursor forward onto one of the current node's children
+    pub fn go_to_child(&mut self, child: usize) -> bool {
+        match self.current_node().children.get(child) {
+            Some(index) => self.go_to(*index).is_ok(),
+            None => false,
+        }
+    }
+
+    // Moves the cursor to any node, keeping the positions it shares with the current path and
+    // replaying the moves from there
+    pub fn go_to(&mut self, index: usize) -> Result<(), MoveError> {
+        let mut route = vec![];
+        let mut ancestor = Some(index);
+        while let Some(node) = ancestor {
+            if let Some(depth) = self.path.iter().position(|(on_path, _)| *on_path == node) {
+                self.path.truncate(depth + 1);
+                break;
+            }
+            route.push(node);
+            ancestor = self.nodes[node].parent;
+        }
+
+        for node in route.into_iter().rev() {
+            let mut state = self.current_state().clone();
+            apply(&mut state, &self.nodes[node])?;
+            self.path.push((node, state));
+        }
+
+        return Ok(());
+    }
+}
+
+// Plays a node's setup stones and move onto the position of its parent
+fn apply(state: &mut BoardState, node: &Node) -> Result<(), MoveError> {
+    for (point, stone) in &node.setup {
+        if point.x >= state.width || point.y >= state.height {
+            return Err(MoveError::OutOfBounds);
+        }
+        state.set_stone(*point, *stone);
+    }
+
+    match node.played {
+        Some(Move::Play(player, point)) => {
+            // Records don't always alternate, such as when one player has extra moves
+            state.current_player = player;
+            state.try_play(point)?;
+        }
+        Some(Move::Pass(player)) => {
+            state.current_player = player;
+            state.pass()?;
+        }
+        None => {}
+    }
+
+    // The count as it was left, which ends the game again if both players had accepted it
+    if state.phase == GamePhase::Scoring {
+        let (width, height) = (state.width, state.height);
+        let dead = sgf::parse_points(node.values(DEAD_PROPERTY), width, height);
+        state.dead_stones.extend(dead.unwrap_or_default());
+        for player in [Player::Black, Player::White] {
+            if node
+                .values(ACCEPTED_PROPERTY)
+                .contains(&sgf::player_to_sgf(player))
+            {
+                let _ = state.accept_score(player);
+            }
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameTree, Move, Node};
+    use crate::go::{BoardState, GamePhase, MoveError, Player};
+    use crate::sgf;
+    use cgmath::point2;
+
+    #[test]
+    fn play_and_navigate() {
+        let mut tree = GameTree::new(BoardState::new(9, 9));
+        assert_eq!(tree.play(point2(2, 2)), Ok(()));
+        assert_eq!(tree.play(point2(6, 6)), Ok(()));
+        assert_eq!(tree.play(point2(6, 6)), Err(MoveError::Occupied));
+        assert_eq!(tree.move_number(), 2);
+
+        assert!(tree.go_to_parent());
+        assert_eq!(tree.current_state().stone_at(point2(6, 6)), None);
+        assert_eq!(tree.current_state().current_player, Player::White);
+
+        assert!(tree.go_to_child(0));
+        assert_eq!(
+            tree.current_node().played,
+            Some(Move::Play(Player::White, point2(6, 6)))
+        );
+        assert_eq!(
+            tree.current_state().stone_at(point2(6, 6)),
+            Some(Player::White)
+        );
+
+        assert!(tree.go_to_parent());
+        assert!(tree.go_to_parent());
+        assert!(!tree.go_to_parent());
+        assert_eq!(tree.current(), 0);
+    }
+
+    #[test]
+    fn variations() {
+        let mut tree = GameTree::new(BoardState::new(9, 9));
+        assert_eq!(tree.play(point2(2, 2)), Ok(()));
+        let main_line = tree.current();
+
+        // Replaying the same move follows the existing node, and a new one branches off
+        tree.go_to_parent();
+        assert_eq!(tree.play(point2(2, 2)), Ok(()));
+        assert_eq!(tree.current(), main_line);
+        tree.go_to_parent();
+        assert_eq!(tree.play(point2(4, 4)), Ok(()));
+        let variation = tree.current();
+        assert_eq!(tree.root().children, vec![main_line, variation]);
+
+        assert_eq!(tree.play(point2(5, 5)), Ok(()));
+        assert_eq!(tree.go_to(main_line), Ok(()));
+        assert_eq!(tree.path().collect::<Vec<_>>(), vec![0, main_line]);
+        assert_eq!(tree.current_state().stone_at(point2(4, 4)), None);
+        assert_eq!(
+            tree.current_state().stone_at(point2(2, 2)),
+            Some(Player::Black)
+        );
+    }
+
+    #[test]
+    fn setup_and_properties() {
+        let mut tree = GameTree::new(BoardState::new(9, 9));
+        tree.root_mut().set_property("PB", "Shusaku");
+
+        let mut node = Node::new(None);
+        node.setup = vec![
+            (point2(0, 0), Some(Player::White)),
+            (point2(1, 0), Some(Player::Black)),
+        ];
+        node.comment = String::from("A position to study");
+        assert_eq!(tree.add_child(node), Ok(()));
+        assert_eq!(
+            tree.current_state().stone_at(point2(0, 0)),
+            Some(Player::White)
+        );
+
+        tree.go_to_parent();
+        assert_eq!(tree.play(point2(0, 1)), Ok(()));
+        assert_eq!(tree.root().children.len(), 2);
+        assert_eq!(tree.root().property("PB"), Some("Shusaku"));
+        assert_eq!(tree.root().property("PW"), None);
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut tree = GameTree::new(BoardState::new(9, 9));
+        assert!(!tree.undo());
+        tree.play(point2(2, 2)).unwrap();
+        let first = tree.current();
+        tree.play(point2(6, 6)).unwrap();
+        tree.play(point2(4, 4)).unwrap();
+
+        assert!(tree.undo());
+        assert!(tree.undo());
+        assert_eq!(tree.current(), first);
+        assert!(tree.redo());
+        assert_eq!(tree.move_number(), 2);
+
+        // A different move is a variation, and after it there is nothing to redo
+        assert!(tree.undo());
+        tree.play(point2(6, 2)).unwrap();
+        assert!(!tree.redo());
+
+        // Redo follows the variation it came back from rather than the main line
+        assert!(tree.undo());
+        assert!(tree.redo());
+        assert_eq!(
+            tree.current_state().stone_at(point2(6, 2)),
+            Some(Player::White)
+        );
+
+        // Without anything undone, redo continues along the main line
+        tree.go_to(0).unwrap();
+        assert!(tree.redo());
+        assert_eq!(tree.current(), first);
+    }
+
+    #[test]
+    fn pass() {
+        let mut tree = GameTree::new(BoardState::new(9, 9));
+        assert_eq!(tree.pass(), Ok(()));
+        assert_eq!(tree.pass(), Ok(()));
+        assert_eq!(tree.move_number(), 2);
+        assert_eq!(tree.play(point2(0, 0)), Err(MoveError::GameOver));
+
+        // Going back to before the passes resumes the game
+        tree.go_to_parent();
+        assert_eq!(tree.play(point2(0, 0)), Ok(()));
+    }
+
+    #[test]
+    fn scoring_is_kept() {
+        let mut tree = GameTree::new(BoardState::new(9, 9));
+        tree.play(point2(2, 2)).unwrap();
+        tree.pass().unwrap();
+        tree.pass().unwrap();
+        assert_eq!(tree.toggle_dead(point2(2, 2)), Ok(()));
+        assert_eq!(tree.accept_score(Player::White), Ok(()));
+
+        // The marks come back after stepping off the node and when the record is read again
+        assert!(tree.undo());
+        assert!(tree.redo());
+        assert!(tree.current_state().dead_stones.contains(&point2(2, 2)));
+        let mut tree = sgf::read(&sgf::write(&tree)).unwrap();
+        while tree.go_to_child(0) {}
+        assert!(tree.current_state().dead_stones.contains(&point2(2, 2)));
+        assert!(tree.current_state().accepted_by.contains(&Player::White));
+
+        assert_eq!(tree.accept_score(Player::Black), Ok(()));
+        assert!(tree.undo());
+        assert!(tree.redo());
+        assert_eq!(tree.current_state().phase, GamePhase::Finished);
+        assert_eq!(tree.current_state().stone_at(point2(2, 2)), None);
+    }
+
+    #[test]
+    fn handicap_stones_are_recorded() {
+        let mut state = BoardState::new(9, 9);
+        state.phase = GamePhase::Setup;
+        state.handicap = 2;
+        let mut tree = GameTree::new(state);
+        assert_eq!(tree.toggle_handicap_stone(point2(2, 2)), Ok(()));
+        assert_eq!(tree.toggle_handicap_stone(point2(6, 6)), Ok(()));
+        assert_eq!(
+            tree.root().setup,
+            vec![
+                (point2(2, 2), Some(Player::Black)),
+                (point2(6, 6), Some(Player::Black))
+            ]
+        );
+
+        tree.current_state_mut().confirm_handicap().unwrap();
+        tree.play(point2(4, 4)).unwrap();
+        assert_eq!(
+            tree.toggle_handicap_stone(point2(2, 6)),
+            Err("Handicap stones can only be placed before the game")
+        );
+    }
+}
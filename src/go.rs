@@ -1,155 +1,1086 @@
+use crate::rules::RuleSet;
 use cgmath::{point2, Point2};
 use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
-// An enum for each player
+// How repeated board positions are restricted
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KoRule {
+    // Only the immediate recapture of a single stone ko is forbidden
+    Simple,
+    // A move may not recreate any earlier board position
+    PositionalSuperko,
+    // A move may not recreate an earlier board position with the same player to move
+    SituationalSuperko,
+}
+
+// An enum for each player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Player {
     Black,
     White,
 }
 
-fn other_player(player: Player) -> Player {
+pub fn other_player(player: Player) -> Player {
     match player {
         Player::Black => Player::White,
         Player::White => Player::Black,
     }
 }
 
+// The stages a game goes through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamePhase {
+    // Black is placing free handicap stones
+    Setup,
+    // Players are taking turns placing stones
+    Playing,
+    // Both players passed in a row, and the result is being counted
+    Scoring,
+    // The game is over
+    Finished,
+}
+
+// How the points at the end of a game are counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoringMethod {
+    // Stones plus surrounded points
+    Area,
+    // Surrounded points plus prisoners
+    Territory,
+}
+
+// Points given to white for moving second, kept as a whole number of half points so that any
+// value a game might use, including negative (reverse) komi, is exact
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Komi {
+    half_points: i32,
+}
+
+impl Komi {
+    pub fn from_half_points(half_points: i32) -> Komi {
+        Komi { half_points }
+    }
+
+    // Parses a komi such as "6.5", "7" or "-0.5". Only whole and half points are allowed
+    pub fn parse(text: &str) -> Option<Komi> {
+        let text = text.trim();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let whole: i32 = whole.parse().ok()?;
+        let half = match fraction.trim_end_matches('0') {
+            "" => 0,
+            "5" => 1,
+            _ => return None,
+        };
+
+        let half_points = whole.checked_mul(2)? + half;
+        Some(Komi::from_half_points(if negative {
+            -half_points
+        } else {
+            half_points
+        }))
+    }
+
+    pub fn points(&self) -> f32 {
+        self.half_points as f32 / 2.0
+    }
+}
+
+impl fmt::Display for Komi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.half_points < 0 { "-" } else { "" };
+        let half_points = self.half_points.abs();
+        match half_points % 2 {
+            0 => write!(f, "{}{}", sign, half_points / 2),
+            _ => write!(f, "{}{}.5", sign, half_points / 2),
+        }
+    }
+}
+
+// How a finished game was decided
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameResult {
+    // The winner, and their margin in points
+    Points(Player, f32),
+    // The winner, after the other player resigned
+    Resignation(Player),
+    // The winner, after the other player ran out of time
+    Time(Player),
+    Draw,
+}
+
+impl fmt::Display for GameResult {
+    // Formats the result the way SGF's RE property does, eg. B+3.5 or W+R
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameResult::Points(winner, margin) => {
+                write!(f, "{}+{}", player_letter(*winner), margin)
+            }
+            GameResult::Resignation(winner) => write!(f, "{}+R", player_letter(*winner)),
+            GameResult::Time(winner) => write!(f, "{}+T", player_letter(*winner)),
+            GameResult::Draw => write!(f, "Draw"),
+        }
+    }
+}
+
+// Why a move was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    // There is already a stone on the point
+    Occupied,
+    // The point retakes a ko immediately
+    Ko,
+    // The move would repeat an earlier position, which the rules forbid
+    Superko,
+    // The move would leave its own group without liberties
+    Suicide,
+    // The point isn't on the board
+    OutOfBounds,
+    // It is the other player's turn, or handicap stones are still being placed
+    NotYourTurn,
+    // The game has ended, or is being scored
+    GameOver,
+    // The server of an online game refused the move, for the given reason. Nothing talks to a
+    // server yet, so this is reserved for the network layer
+    #[allow(dead_code)]
+    RemoteRejected(String),
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::Occupied => write!(f, "Can't play where a piece already is"),
+            MoveError::Ko => write!(f, "Can't play in the ko"),
+            MoveError::Superko => write!(f, "Can't repeat an earlier position"),
+            MoveError::Suicide => write!(f, "Self capture isn't allowed"),
+            MoveError::OutOfBounds => write!(f, "That point is off the board"),
+            MoveError::NotYourTurn => write!(f, "It's not your turn"),
+            MoveError::GameOver => write!(f, "The game is over"),
+            MoveError::RemoteRejected(reason) => {
+                write!(f, "The server refused the move: {}", reason)
+            }
+        }
+    }
+}
+
+fn player_letter(player: Player) -> &'static str {
+    match player {
+        Player::Black => "B",
+        Player::White => "W",
+    }
+}
+
+// Each player's total points at the end of a game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Score {
+    pub black: f32,
+    pub white: f32,
+}
+
+impl Score {
+    pub fn result(&self) -> GameResult {
+        if self.black > self.white {
+            GameResult::Points(Player::Black, self.black - self.white)
+        } else if self.white > self.black {
+            GameResult::Points(Player::White, self.white - self.black)
+        } else {
+            GameResult::Draw
+        }
+    }
+}
+
+pub const MIN_BOARD_SIZE: usize = 2;
+pub const MAX_BOARD_SIZE: usize = 25;
+
+// Parses a board size such as "19x19", "9x13" or just "19" into a width and height
+pub fn parse_board_size(text: &str) -> Result<(usize, usize), String> {
+    let (width, height) = text.split_once('x').unwrap_or((text, text));
+    let parse = |length: &str| length.trim().parse::<usize>().ok();
+
+    match (parse(width), parse(height)) {
+        (Some(width), Some(height))
+            if (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&width)
+                && (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&height) =>
+        {
+            Ok((width, height))
+        }
+        _ => Err(format!(
+            "Board sizes must be between {} and {}",
+            MIN_BOARD_SIZE, MAX_BOARD_SIZE
+        )),
+    }
+}
+
+// The traditional fixed handicap points, in the order they are filled, or None if the board is too
+// small for that many stones
+pub fn handicap_points(width: usize, height: usize, handicap: u32) -> Option<Vec<Point2<usize>>> {
+    if handicap < 2 {
+        return Some(vec![]);
+    }
+
+    if width.min(height) < 7 || handicap > 9 {
+        return None;
+    }
+
+    // Five or more stones use the center and sides, which only odd sizes have
+    if handicap > 4 && (width.is_multiple_of(2) || height.is_multiple_of(2)) {
+        return None;
+    }
+
+    let edge_distance = |length: usize| if length >= 13 { 3 } else { 2 };
+    let left = edge_distance(width);
+    let right = width - 1 - edge_distance(width);
+    let top = edge_distance(height);
+    let bottom = height - 1 - edge_distance(height);
+    let middle = point2(width / 2, height / 2);
+
+    let corners = vec![
+        point2(right, top),
+        point2(left, bottom),
+        point2(right, bottom),
+        point2(left, top),
+    ];
+    let sides = vec![
+        point2(left, middle.y),
+        point2(right, middle.y),
+        point2(middle.x, top),
+        point2(middle.x, bottom),
+    ];
+
+    let mut points = corners;
+    points.truncate(handicap.min(4) as usize);
+    match handicap {
+        5 => points.push(middle),
+        6 => points.extend_from_slice(&sides[..2]),
+        7 => {
+            points.extend_from_slice(&sides[..2]);
+            points.push(middle);
+        }
+        8 => points.extend_from_slice(&sides),
+        9 => {
+            points.extend_from_slice(&sides);
+            points.push(middle);
+        }
+        _ => {}
+    }
+
+    return Some(points);
+}
+
+// Mixes the bits of a seed into a well distributed pseudo random number (SplitMix64)
+pub fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    return z ^ (z >> 31);
+}
+
+// The Zobrist key for a stone of the given color on the given point
+fn zobrist_key(point: Point2<usize>, player: Player) -> u64 {
+    let color = match player {
+        Player::Black => 0,
+        Player::White => 1,
+    };
+    return splitmix64(((point.x as u64) << 32 | (point.y as u64) << 1 | color) + 1);
+}
+
+// The Zobrist key which is mixed in when it is the given player's turn
+fn to_move_key(player: Player) -> u64 {
+    match player {
+        Player::Black => 0,
+        Player::White => splitmix64(0),
+    }
+}
+
 // A struct representing the state of a Go Board
+#[derive(Clone)]
 pub struct BoardState {
-    // The size of the board
-    pub size: usize,
+    // The size of the board, which may be rectangular
+    pub width: usize,
+    pub height: usize,
     // The current player
     pub current_player: Player,
-    // The current board state
-    pub board: Vec<Vec<Option<Player>>>,
     pub last_move: Option<Point2<usize>>,
     pub ko: Option<Point2<usize>>,
 
+    // The number of stones of each color which have been captured
     pub captured_black: u32,
     pub captured_white: u32,
-    pub komi_minus_half: u32,
+    pub komi: Komi,
+    // The number of handicap stones black started with
+    pub handicap: u32,
+    // Where the handicap stones were placed, which SGF records as setup stones (AB)
+    pub handicap_stones: Vec<Point2<usize>>,
+
+    pub phase: GamePhase,
+    // How many passes have been made in a row. Two ends the game
+    pub consecutive_passes: u32,
+    pub result: Option<GameResult>,
+
+    // Stones which both players agree are dead, while the game is being scored
+    pub dead_stones: HashSet<Point2<usize>>,
+    // The players who have accepted the current count
+    pub accepted_by: HashSet<Player>,
+
+    pub rules: RuleSet,
+
+    // The stones on the board, row by row. Points are looked up with stone_at
+    stones: Vec<Option<Player>>,
+    // Union-find links from each stone towards the root stone of its group
+    parent: Vec<usize>,
+    // Links the stones of each group into a ring, so a group can be walked without a flood fill
+    next_stone: Vec<usize>,
+    // The number of stones in each group, stored on its root
+    group_size: Vec<u32>,
+    // How many times an empty point is next to one of a group's stones, stored on its root. A
+    // point next to several of the stones is counted once for each, so this overstates the
+    // liberties, but it is only zero when there are none
+    pseudo_liberties: Vec<u32>,
+    // Zobrist hash of the stones on the board, updated with every stone placed or removed
+    hash: u64,
+    // Hashes of every position so far, including the player to move, under superko rules. Copies
+    // of a state share this until one of them plays a move
+    history: Arc<HashSet<u64>>,
 }
 
-impl BoardState {
-    pub fn new(size: usize) -> BoardState {
-        let mut board = Vec::new();
-        for _ in 0..size {
-            let mut row = Vec::new();
-            for _ in 0..size {
-                row.push(None);
-            }
-            board.push(row);
-        }
+// Two states are equal when they have the same stones, the same player to move and the same ko
+// point, in the same phase of the game. How they got there, including any superko history, isn't
+// compared
+impl PartialEq for BoardState {
+    fn eq(&self, other: &BoardState) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self.hash == other.hash
+            && self.stones == other.stones
+            && self.current_player == other.current_player
+            && self.ko == other.ko
+            && self.phase == other.phase
+    }
+}
 
-        BoardState {
-            size,
+impl Eq for BoardState {}
+
+impl Hash for BoardState {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.situation_hash(self.current_player).hash(state);
+        self.ko.hash(state);
+    }
+}
+
+// What a legal move does to the board, worked out before anything is changed
+struct MoveEffect {
+    index: usize,
+    // The roots of the opponent's groups which are captured
+    captured: [Option<usize>; 4],
+    // The roots of the player's own groups which the new stone joins
+    joined: [Option<usize>; 4],
+    // Whether the player's own group is left without liberties and removed
+    suicide: bool,
+}
+
+impl BoardState {
+    pub fn new(width: usize, height: usize) -> BoardState {
+        let points = width * height;
+        let mut state = BoardState {
+            width,
+            height,
             current_player: Player::Black,
-            board,
             last_move: None,
             ko: None,
             captured_black: 0,
             captured_white: 0,
-            komi_minus_half: 6,
+            komi: RuleSet::Japanese.default_komi(),
+            handicap: 0,
+            handicap_stones: vec![],
+            phase: GamePhase::Playing,
+            consecutive_passes: 0,
+            result: None,
+            dead_stones: HashSet::new(),
+            accepted_by: HashSet::new(),
+            rules: RuleSet::Japanese,
+            stones: vec![None; points],
+            parent: (0..points).collect(),
+            next_stone: (0..points).collect(),
+            group_size: vec![0; points],
+            pseudo_liberties: vec![0; points],
+            hash: 0,
+            history: Arc::new(HashSet::new()),
+        };
+        let hash = state.situation_hash(state.current_player);
+        Arc::make_mut(&mut state.history).insert(hash);
+        state
+    }
+
+    // Sets up a handicap game. Under rules with fixed placement the standard handicap stones are
+    // placed straight away, otherwise black places them anywhere during the setup phase. Either
+    // way white moves first. A handicap of one places no stones, but black moves first without komi
+    pub fn setup_handicap(self: &mut BoardState, handicap: u32) -> Result<(), String> {
+        if handicap == 0 {
+            return Ok(());
+        }
+
+        let stones = if handicap == 1 || self.rules.free_handicap() {
+            Some(vec![])
+        } else {
+            handicap_points(self.width, self.height, handicap)
+        };
+        let stones = match stones {
+            Some(stones) if (handicap as usize) < self.width * self.height => stones,
+            _ => {
+                return Err(format!(
+                    "A handicap of {} isn't possible on a {}x{} board",
+                    handicap, self.width, self.height
+                ))
+            }
+        };
+
+        self.handicap = handicap;
+        self.komi = self.rules.komi_for_handicap(handicap);
+        if handicap == 1 {
+            return Ok(());
+        }
+
+        if self.rules.free_handicap() {
+            self.phase = GamePhase::Setup;
+            return Ok(());
+        }
+
+        for stone in stones {
+            self.set_stone(stone, Some(Player::Black));
+            self.handicap_stones.push(stone);
         }
+        self.start_after_handicap();
+        return Ok(());
     }
 
-    pub fn captures_made_by(&self, player: Player) -> u32 {
-        match player {
-            Player::Black => self.captured_black,
-            Player::White => self.captured_white,
+    // Places or removes one of black's handicap stones during the setup phase
+    pub fn toggle_handicap_stone(
+        self: &mut BoardState,
+        point: Point2<usize>,
+    ) -> Result<(), &'static str> {
+        if self.phase != GamePhase::Setup {
+            return Err("Handicap stones can only be placed before the game");
+        }
+
+        if let Some(index) = self.handicap_stones.iter().position(|x| *x == point) {
+            self.handicap_stones.remove(index);
+            self.set_stone(point, None);
+            return Ok(());
+        }
+
+        if self.handicap_stones.len() >= self.handicap as usize {
+            return Err("All of the handicap stones are placed");
         }
+
+        self.handicap_stones.push(point);
+        self.set_stone(point, Some(Player::Black));
+        return Ok(());
     }
 
-    pub fn try_play(self: &mut BoardState, point: Point2<usize>) -> Result<(), &str> {
-        // Can't play where a piece already is
-        if self.board[point.x][point.y].is_some() {
-            return Err("Can't play where a piece already is");
+    // Ends the setup phase once all of the handicap stones are placed
+    pub fn confirm_handicap(self: &mut BoardState) -> Result<(), &'static str> {
+        if self.phase != GamePhase::Setup {
+            return Err("Handicap stones can only be placed before the game");
+        }
+        if self.handicap_stones.len() < self.handicap as usize {
+            return Err("Not all of the handicap stones are placed");
         }
 
-        // Ko rule
-        if let Some(ko) = self.ko {
-            if point == ko {
-                return Err("Can't play in the ko");
+        self.phase = GamePhase::Playing;
+        self.start_after_handicap();
+        return Ok(());
+    }
+
+    fn start_after_handicap(&mut self) {
+        self.current_player = Player::White;
+        self.history = Arc::new(HashSet::from([self.situation_hash(self.current_player)]));
+    }
+
+    // A hash of the stones on the board, suitable for detecting repeated positions
+    #[cfg(test)]
+    pub fn position_hash(&self) -> u64 {
+        self.hash
+    }
+
+    // A hash of the stones on the board together with the player to move
+    pub fn situation_hash(&self, to_move: Player) -> u64 {
+        self.hash ^ to_move_key(to_move)
+    }
+
+    pub fn stone_at(&self, point: Point2<usize>) -> Option<Player> {
+        self.stones[self.index(point)]
+    }
+
+    // Puts a stone on the board or clears a point, without playing a move, so nothing is captured
+    pub fn set_stone(&mut self, point: Point2<usize>, stone: Option<Player>) {
+        let index = self.index(point);
+        if let (None, Some(player)) = (self.stones[index], stone) {
+            self.add_stone(index, player);
+            return;
+        }
+
+        // Taking a stone away can split its group, so the groups are worked out again
+        let mut stones = self.stones.clone();
+        stones[index] = stone;
+        self.hash = 0;
+        self.stones = vec![None; stones.len()];
+        for (index, stone) in stones.into_iter().enumerate() {
+            if let Some(player) = stone {
+                self.add_stone(index, player);
             }
         }
+    }
 
-        self.board[point.x][point.y] = Some(self.current_player);
+    fn index(&self, point: Point2<usize>) -> usize {
+        point.y * self.width + point.x
+    }
 
-        // Capture other pieces
-        for neighbor in self.get_neighbours(&point) {
-            if self.board[neighbor.x][neighbor.y] != Some(other_player(self.current_player)) {
-                continue;
+    fn point(&self, index: usize) -> Point2<usize> {
+        point2(index % self.width, index / self.width)
+    }
+
+    // The indices of the points next to the given one
+    fn adjacent(&self, index: usize) -> impl Iterator<Item = usize> {
+        let (x, y) = (index % self.width, index / self.width);
+        [
+            (x > 0).then(|| index - 1),
+            (x + 1 < self.width).then(|| index + 1),
+            (y > 0).then(|| index - self.width),
+            (y + 1 < self.height).then(|| index + self.width),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    // Finds the root stone of the group containing the stone at the given index
+    fn find(&self, mut index: usize) -> usize {
+        while self.parent[index] != index {
+            index = self.parent[index];
+        }
+        index
+    }
+
+    // Places a stone on an empty point, joining it to any neighbouring groups of its color
+    fn add_stone(&mut self, index: usize, player: Player) {
+        self.stones[index] = Some(player);
+        self.hash ^= zobrist_key(self.point(index), player);
+        self.parent[index] = index;
+        self.next_stone[index] = index;
+        self.group_size[index] = 1;
+        self.pseudo_liberties[index] = 0;
+
+        for neighbour in self.adjacent(index) {
+            match self.stones[neighbour] {
+                None => self.pseudo_liberties[index] += 1,
+                Some(_) => {
+                    let root = self.find(neighbour);
+                    self.pseudo_liberties[root] -= 1;
+                }
             }
+        }
 
-            let line = self.get_line(&neighbor);
-            let liberties = self.get_liberties(&line);
-            if liberties.len() == 0 {
-                for point in line {
-                    self.board[point.x][point.y] = None;
-                    if self.current_player == Player::Black {
-                        self.captured_black += 1;
-                    } else {
-                        self.captured_white += 1;
-                    }
+        for neighbour in self.adjacent(index) {
+            if self.stones[neighbour] == Some(player) {
+                self.join(index, neighbour);
+            }
+        }
+    }
+
+    fn join(&mut self, first: usize, second: usize) {
+        let (mut root, mut child) = (self.find(first), self.find(second));
+        if root == child {
+            return;
+        }
+
+        // The smaller group goes under the larger one, which keeps the links short
+        if self.group_size[root] < self.group_size[child] {
+            std::mem::swap(&mut root, &mut child);
+        }
+        self.parent[child] = root;
+        self.group_size[root] += self.group_size[child];
+        self.pseudo_liberties[root] += self.pseudo_liberties[child];
+        self.next_stone.swap(root, child);
+    }
+
+    // Takes the group with the given root off the board, returning how many stones it had
+    fn remove_group(&mut self, root: usize) -> u32 {
+        let player = self.stones[root].unwrap();
+        let size = self.group_size[root];
+
+        let mut index = root;
+        loop {
+            self.stones[index] = None;
+            self.hash ^= zobrist_key(self.point(index), player);
+            for neighbour in self.adjacent(index) {
+                if self.stones[neighbour] == Some(other_player(player)) {
+                    let neighbour_root = self.find(neighbour);
+                    self.pseudo_liberties[neighbour_root] += 1;
                 }
             }
+
+            index = self.next_stone[index];
+            if index == root {
+                break;
+            }
+        }
+
+        return size;
+    }
+
+    // The stones of the group with the given root
+    fn group(&self, root: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut index = Some(root);
+        std::iter::from_fn(move || {
+            let current = index?;
+            let next = self.next_stone[current];
+            index = if next == root { None } else { Some(next) };
+            Some(current)
+        })
+    }
+
+    // Whether the given empty point is the last liberty of the group with the given root
+    fn is_last_liberty(&self, root: usize, index: usize) -> bool {
+        let adjacent_stones = self
+            .adjacent(index)
+            .filter(|neighbour| self.stones[*neighbour].is_some() && self.find(*neighbour) == root)
+            .count();
+        self.pseudo_liberties[root] == adjacent_stones as u32
+    }
+
+    // The hash of the position with the group of the given root taken off the board
+    fn hash_without(&self, hash: u64, root: usize) -> u64 {
+        let player = self.stones[root].unwrap();
+        self.group(root).fold(hash, |hash, index| {
+            hash ^ zobrist_key(self.point(index), player)
+        })
+    }
+
+    pub fn captures_made_by(&self, player: Player) -> u32 {
+        match player {
+            Player::Black => self.captured_white,
+            Player::White => self.captured_black,
         }
+    }
+
+    fn check_playing(&self) -> Result<(), MoveError> {
+        match self.phase {
+            GamePhase::Setup => Err(MoveError::NotYourTurn),
+            GamePhase::Playing => Ok(()),
+            GamePhase::Scoring | GamePhase::Finished => Err(MoveError::GameOver),
+        }
+    }
 
-        // If the current player does not have any liberties, return false
-        // Note that this will never be true if any pieces were just captured
-        let played_line = self.get_line(&point);
-        let liberties = self.get_liberties(&played_line);
-        if liberties.len() == 0 {
-            self.board[point.x][point.y] = None;
-            return Err("Self capture");
+    // Whether the given player passed since the last stone was played
+    pub fn has_passed(&self, player: Player) -> bool {
+        match self.consecutive_passes {
+            0 => false,
+            1 => self.current_player != player,
+            _ => true,
         }
+    }
+
+    pub fn pass(self: &mut BoardState) -> Result<(), MoveError> {
+        self.check_playing()?;
 
+        // Passing lifts the ko restriction. Passes are not recorded in the position history, so
+        // superko only compares the positions reached by playing stones
+        self.ko = None;
+        self.last_move = None;
         self.current_player = other_player(self.current_player);
-        self.last_move = Some(point);
+        self.consecutive_passes += 1;
+
+        if self.consecutive_passes >= 2 {
+            self.phase = GamePhase::Scoring;
+        }
 
         return Ok(());
     }
 
-    pub fn get_line(self: &mut BoardState, point: &Point2<usize>) -> Vec<Point2<usize>> {
-        let color = self.board[point.x][point.y];
-        if color == None {
-            return vec![];
+    pub fn resign(self: &mut BoardState, player: Player) -> Result<(), &'static str> {
+        if self.phase == GamePhase::Finished {
+            return Err("The game is over");
         }
 
-        let mut to_visit = vec![];
+        self.phase = GamePhase::Finished;
+        self.result = Some(GameResult::Resignation(other_player(player)));
+        return Ok(());
+    }
+
+    // Ends the game when a player's clock runs out while they are playing
+    pub fn time_out(self: &mut BoardState, player: Player) -> Result<(), &'static str> {
+        if self.phase != GamePhase::Playing {
+            return Err("The clocks only run while the game is being played");
+        }
+
+        self.phase = GamePhase::Finished;
+        self.result = Some(GameResult::Time(other_player(player)));
+        return Ok(());
+    }
+
+    // Marks or unmarks the group at the given point as dead. Any change to the dead stones means
+    // both players need to accept the count again
+    pub fn toggle_dead(self: &mut BoardState, point: Point2<usize>) -> Result<(), &'static str> {
+        if self.phase != GamePhase::Scoring {
+            return Err("The game is not being scored");
+        }
+        if self.stone_at(point).is_none() {
+            return Err("There is no group there");
+        }
+
+        let line = self.get_line(&point);
+        if self.dead_stones.contains(&point) {
+            for stone in line {
+                self.dead_stones.remove(&stone);
+            }
+        } else {
+            self.dead_stones.extend(line);
+        }
+
+        self.accepted_by.clear();
+        return Ok(());
+    }
+
+    // Agrees to the current count on behalf of a player. The game ends once both players agree
+    pub fn accept_score(self: &mut BoardState, player: Player) -> Result<(), &'static str> {
+        if self.phase != GamePhase::Scoring {
+            return Err("The game is not being scored");
+        }
+
+        self.accepted_by.insert(player);
+        if self.accepted_by.len() < 2 {
+            return Ok(());
+        }
+
+        // Dead stones are taken off the board as prisoners
+        for stone in self.dead_stones.drain().collect::<Vec<_>>() {
+            match self.stone_at(stone) {
+                Some(Player::Black) => self.captured_black += 1,
+                Some(Player::White) => self.captured_white += 1,
+                None => {}
+            }
+            self.set_stone(stone, None);
+        }
+
+        self.phase = GamePhase::Finished;
+        self.result = Some(self.score().result());
+        return Ok(());
+    }
+
+    pub fn score(&self) -> Score {
+        match self.rules.scoring() {
+            ScoringMethod::Area => self.area_score(),
+            ScoringMethod::Territory => self.territory_score(),
+        }
+    }
+
+    // Chinese style scoring: each player gets a point for every living stone they have on the
+    // board, and every point in the territory they surround. White also gets komi, and any
+    // compensation for handicap stones
+    pub fn area_score(&self) -> Score {
+        let compensation = self.rules.handicap_compensation().points(self.handicap);
+        let mut score = Score {
+            black: 0.0,
+            white: self.komi.points() + compensation,
+        };
+
+        for x in 0..self.width {
+            for y in 0..self.height {
+                if self.dead_stones.contains(&point2(x, y)) {
+                    continue;
+                }
+                match self.stone_at(point2(x, y)) {
+                    Some(Player::Black) => score.black += 1.0,
+                    Some(Player::White) => score.white += 1.0,
+                    None => {}
+                }
+            }
+        }
+
+        for (region, owner) in self.get_regions() {
+            match owner {
+                Some(Player::Black) => score.black += region.len() as f32,
+                Some(Player::White) => score.white += region.len() as f32,
+                None => {}
+            }
+        }
+
+        return score;
+    }
+
+    // Japanese style scoring: each player gets a point for every empty point in their territory,
+    // and every prisoner they took, including dead stones left on the board. White also gets komi
+    pub fn territory_score(&self) -> Score {
+        let mut score = Score {
+            black: self.captured_white as f32,
+            white: self.captured_black as f32 + self.komi.points(),
+        };
+
+        for (region, owner) in self.get_regions() {
+            for point in region {
+                let points = match self.stone_at(point) {
+                    // A dead stone is a prisoner, and the point it sits on is territory
+                    Some(_) => 2.0,
+                    None => 1.0,
+                };
+                match owner {
+                    Some(Player::Black) => score.black += points,
+                    Some(Player::White) => score.white += points,
+                    None => {}
+                }
+            }
+        }
+
+        return score;
+    }
+
+    // Splits the board into regions of empty points and dead stones, along with the player who
+    // owns each region
+    pub fn get_regions(&self) -> Vec<(Vec<Point2<usize>>, Option<Player>)> {
+        let mut regions = vec![];
         let mut visited = HashSet::new();
-        let mut line = vec![];
+        for x in 0..self.width {
+            for y in 0..self.height {
+                let point = point2(x, y);
+                if visited.contains(&point) || self.is_alive(&point) {
+                    continue;
+                }
+
+                let (region, owner) = self.get_region(&point);
+                visited.extend(region.iter().cloned());
+                regions.push((region, owner));
+            }
+        }
 
-        to_visit.push(point.clone());
+        return regions;
+    }
 
-        while to_visit.len() > 0 {
-            let current = to_visit.pop().unwrap();
-            visited.insert(current);
+    // Finds the region of empty points and dead stones containing the given point, and the player
+    // who surrounds it, if only one player's living stones border the region
+    pub fn get_region(&self, point: &Point2<usize>) -> (Vec<Point2<usize>>, Option<Player>) {
+        let mut to_visit = vec![*point];
+        let mut visited = HashSet::new();
+        let mut region = vec![];
+        let mut borders_black = false;
+        let mut borders_white = false;
 
-            if !(self.board[current.x][current.y] == color) {
+        while let Some(current) = to_visit.pop() {
+            if !visited.insert(current) {
                 continue;
             }
 
-            line.push(current);
-            let neighbors = self.get_neighbours(&current);
-            for neighbor in neighbors {
+            if self.is_alive(&current) {
+                match self.stone_at(current) {
+                    Some(Player::Black) => borders_black = true,
+                    Some(Player::White) => borders_white = true,
+                    None => {}
+                }
+                continue;
+            }
+
+            region.push(current);
+            for neighbor in self.get_neighbours(&current) {
                 if !visited.contains(&neighbor) {
-                    to_visit.push(neighbor.clone());
+                    to_visit.push(neighbor);
+                }
+            }
+        }
+
+        let owner = match (borders_black, borders_white) {
+            (true, false) => Some(Player::Black),
+            (false, true) => Some(Player::White),
+            _ => None,
+        };
+
+        return (region, owner);
+    }
+
+    fn is_alive(&self, point: &Point2<usize>) -> bool {
+        self.stone_at(*point).is_some() && !self.dead_stones.contains(point)
+    }
+
+    pub fn is_legal(&self, point: Point2<usize>) -> bool {
+        self.check_move(point).is_ok()
+    }
+
+    // Every point the current player could play on, not including passing
+    pub fn legal_moves(&self) -> Vec<Point2<usize>> {
+        (0..self.stones.len())
+            .map(|index| self.point(index))
+            .filter(|point| self.is_legal(*point))
+            .collect()
+    }
+
+    pub fn try_play(self: &mut BoardState, point: Point2<usize>) -> Result<(), MoveError> {
+        let effect = self.check_move(point)?;
+        let player = self.current_player;
+        let next_player = other_player(player);
+
+        self.add_stone(effect.index, player);
+        let mut captured_stones = 0;
+        for root in effect.captured.iter().flatten() {
+            captured_stones += self.remove_group(*root);
+        }
+        if effect.suicide {
+            let suicided = self.remove_group(self.find(effect.index));
+            match player {
+                Player::Black => self.captured_black += suicided,
+                Player::White => self.captured_white += suicided,
+            }
+        }
+        match player {
+            Player::Black => self.captured_white += captured_stones,
+            Player::White => self.captured_black += captured_stones,
+        }
+        // Only superko looks back through the history. Leaving it alone otherwise saves copying it
+        // in every copy of the state which plays a move, as the computer's playouts do
+        if self.rules.ko_rule() != KoRule::Simple {
+            let hash = self.situation_hash(next_player);
+            Arc::make_mut(&mut self.history).insert(hash);
+        }
+
+        // A single stone which captured a single stone, and is left in atari, can be recaptured
+        // immediately. Forbid that recapture for the next move only.
+        self.ko = None;
+        let joins_group = effect.joined.iter().flatten().count() > 0;
+        let liberties = self
+            .adjacent(effect.index)
+            .filter(|neighbour| self.stones[*neighbour].is_none())
+            .count();
+        if captured_stones == 1 && !joins_group && liberties == 1 {
+            let root = effect.captured.iter().flatten().next().unwrap();
+            self.ko = Some(self.point(*root));
+        }
+
+        self.current_player = next_player;
+        self.last_move = Some(point);
+        self.consecutive_passes = 0;
+
+        return Ok(());
+    }
+
+    // Works out what playing the given point would do, without changing anything, so an illegal
+    // move never needs to be undone
+    fn check_move(&self, point: Point2<usize>) -> Result<MoveEffect, MoveError> {
+        self.check_playing()?;
+        if point.x >= self.width || point.y >= self.height {
+            return Err(MoveError::OutOfBounds);
+        }
+
+        // Can't play where a piece already is
+        let index = self.index(point);
+        if self.stones[index].is_some() {
+            return Err(MoveError::Occupied);
+        }
+
+        // Ko rule
+        if let Some(ko) = self.ko {
+            if point == ko {
+                return Err(MoveError::Ko);
+            }
+        }
+
+        // Neighbouring groups with no other liberty are either captured or, for the player's own
+        // groups, joined into a group which might be suicide
+        let player = self.current_player;
+        let mut captured = [None; 4];
+        let mut joined = [None; 4];
+        let mut has_liberty = false;
+        for (i, neighbour) in self.adjacent(index).enumerate() {
+            let stone = match self.stones[neighbour] {
+                Some(stone) => stone,
+                None => {
+                    has_liberty = true;
+                    continue;
+                }
+            };
+
+            let root = self.find(neighbour);
+            let last_liberty = self.is_last_liberty(root, index);
+            if stone == player {
+                has_liberty |= !last_liberty;
+                if !joined.contains(&Some(root)) {
+                    joined[i] = Some(root);
+                }
+            } else if last_liberty && !captured.contains(&Some(root)) {
+                captured[i] = Some(root);
+            }
+        }
+        let captures = captured.iter().flatten().count() > 0;
+        let joins_group = joined.iter().flatten().count() > 0;
+
+        // If the current player does not have any liberties, the move is suicide
+        // Note that this will never be true if any pieces are captured
+        let suicide = !has_liberty && !captures;
+        // Suicide of a single stone would leave the board unchanged, so it is never allowed
+        if suicide && (!self.rules.suicide_allowed() || !joins_group) {
+            return Err(MoveError::Suicide);
+        }
+
+        // Superko rules forbid recreating any earlier position
+        let ko_rule = self.rules.ko_rule();
+        if ko_rule != KoRule::Simple {
+            let mut hash = self.hash ^ zobrist_key(point, player);
+            for root in captured.iter().flatten() {
+                hash = self.hash_without(hash, *root);
+            }
+            if suicide {
+                hash ^= zobrist_key(point, player);
+                for root in joined.iter().flatten() {
+                    hash = self.hash_without(hash, *root);
+                }
+            }
+
+            let repeated = match ko_rule {
+                KoRule::Simple => false,
+                KoRule::PositionalSuperko => {
+                    self.history.contains(&(hash ^ to_move_key(Player::Black)))
+                        || self.history.contains(&(hash ^ to_move_key(Player::White)))
                 }
+                KoRule::SituationalSuperko => {
+                    let next_player = other_player(player);
+                    self.history.contains(&(hash ^ to_move_key(next_player)))
+                }
+            };
+            if repeated {
+                return Err(MoveError::Superko);
             }
         }
 
-        return line;
+        return Ok(MoveEffect {
+            index,
+            captured,
+            joined,
+            suicide,
+        });
+    }
+
+    pub fn get_line(&self, point: &Point2<usize>) -> Vec<Point2<usize>> {
+        let index = self.index(*point);
+        if self.stones[index].is_none() {
+            return vec![];
+        }
+
+        let root = self.find(index);
+        return self.group(root).map(|index| self.point(index)).collect();
     }
 
-    pub fn get_liberties(self: &mut BoardState, line: &Vec<Point2<usize>>) -> Vec<Point2<usize>> {
+    // Groups keep count of their liberties as stones are played, so the board itself no longer
+    // needs to list them
+    #[allow(dead_code)]
+    pub fn get_liberties(&self, line: &Vec<Point2<usize>>) -> Vec<Point2<usize>> {
         let mut liberties = HashSet::new();
 
         for point in line {
-            let neighbors = self.get_neighbours(&point);
+            let neighbors = self.get_neighbours(point);
             for neighbor in neighbors {
-                if self.board[neighbor.x][neighbor.y].is_none() {
+                if self.stone_at(neighbor).is_none() {
                     liberties.insert(neighbor);
                 }
             }
@@ -158,19 +1089,19 @@ impl BoardState {
         return liberties.into_iter().collect();
     }
 
-    pub fn get_neighbours(self: &mut BoardState, point: &Point2<usize>) -> Vec<Point2<usize>> {
+    pub fn get_neighbours(&self, point: &Point2<usize>) -> Vec<Point2<usize>> {
         let mut neighbours = vec![];
 
         if point.x > 0 {
             neighbours.push(point2(point.x - 1, point.y));
         }
-        if point.x < self.size - 1 {
+        if point.x < self.width - 1 {
             neighbours.push(point2(point.x + 1, point.y));
         }
         if point.y > 0 {
             neighbours.push(point2(point.x, point.y - 1));
         }
-        if point.y < self.size - 1 {
+        if point.y < self.height - 1 {
             neighbours.push(point2(point.x, point.y + 1));
         }
 
@@ -180,35 +1111,637 @@ impl BoardState {
 
 #[cfg(test)]
 mod tests {
+    use super::MoveError;
+    use crate::rules::RuleSet;
     use cgmath::point2;
 
+    fn play_all(state: &mut super::BoardState, moves: &[(usize, usize)]) {
+        for (x, y) in moves {
+            assert_eq!(state.try_play(point2(*x, *y)), Ok(()));
+        }
+    }
+
     #[test]
     fn only_play_each_once() {
-        let mut state = super::BoardState::new(19);
+        let mut state = super::BoardState::new(19, 19);
         assert_eq!(state.try_play(point2(10, 10)), Ok(()));
-        assert_eq!(state.try_play(point2(10, 10)), Err("Can't play where a piece already is"));
+        assert_eq!(state.try_play(point2(10, 10)), Err(MoveError::Occupied));
     }
 
     #[test]
     fn ko() {
-        let mut state = super::BoardState::new(19);
+        let mut state = super::BoardState::new(19, 19);
         state.ko = Some(point2(10, 10));
-        assert_eq!(state.try_play(point2(10, 10)), Err("Can't play in the ko"));
+        assert_eq!(state.try_play(point2(10, 10)), Err(MoveError::Ko));
     }
 
     #[test]
     fn capture() {
-        let mut state = super::BoardState::new(19);
+        let mut state = super::BoardState::new(19, 19);
 
-        state.board[9][10] = Some(super::Player::Black);
-        state.board[10][9] = Some(super::Player::Black);
-        state.board[11][10] = Some(super::Player::Black);
-        state.board[10][10] = Some(super::Player::White);
+        state.set_stone(point2(9, 10), Some(super::Player::Black));
+        state.set_stone(point2(10, 9), Some(super::Player::Black));
+        state.set_stone(point2(11, 10), Some(super::Player::Black));
+        state.set_stone(point2(10, 10), Some(super::Player::White));
 
         assert_eq!(state.try_play(point2(10, 11)), Ok(()));
 
         assert_eq!(state.captured_white, 1);
         assert_eq!(state.captured_black, 0);
-        assert_eq!(state.board[10][10], None);
+        assert_eq!(state.stone_at(point2(10, 10)), None);
+    }
+
+    #[test]
+    fn capture_block() {
+        let mut state = super::BoardState::new(5, 5);
+
+        // A square group can be reached along more than one path, but each stone counts once
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            state.set_stone(point2(x, y), Some(super::Player::White));
+        }
+        for (x, y) in [(2, 0), (2, 1), (0, 2)] {
+            state.set_stone(point2(x, y), Some(super::Player::Black));
+        }
+
+        assert_eq!(state.get_line(&point2(0, 0)).len(), 4);
+        let line = state.get_line(&point2(0, 0));
+        assert_eq!(state.get_liberties(&line), vec![point2(1, 2)]);
+        assert_eq!(state.try_play(point2(1, 2)), Ok(()));
+        assert_eq!(state.captured_white, 4);
+        assert_eq!(state.stone_at(point2(1, 1)), None);
+    }
+
+    #[test]
+    fn remove_stone_splits_group() {
+        let mut state = super::BoardState::new(5, 5);
+        for x in 0..5 {
+            state.set_stone(point2(x, 2), Some(super::Player::Black));
+        }
+        assert_eq!(state.get_line(&point2(0, 2)).len(), 5);
+
+        state.set_stone(point2(2, 2), None);
+        assert_eq!(state.get_line(&point2(0, 2)).len(), 2);
+        assert_eq!(state.get_line(&point2(4, 2)).len(), 2);
+        let line = state.get_line(&point2(0, 2));
+        assert_eq!(state.get_liberties(&line).len(), 5);
+    }
+
+    #[test]
+    fn legal_moves() {
+        let mut state = super::BoardState::new(3, 3);
+        // White can't play in the corner, which would be suicide, or in the ko
+        play_all(&mut state, &[(1, 0), (2, 2), (0, 1)]);
+        state.ko = Some(point2(2, 0));
+
+        let before = state.clone();
+        let moves = state.legal_moves();
+        assert_eq!(moves.len(), 4);
+        assert!(!moves.contains(&point2(0, 0)));
+        assert!(!moves.contains(&point2(2, 0)));
+        assert!(!state.is_legal(point2(1, 0)));
+        assert!(!state.is_legal(point2(3, 0)));
+        assert_eq!(state.try_play(point2(0, 3)), Err(MoveError::OutOfBounds));
+        assert!(state.is_legal(point2(1, 1)));
+        assert!(state == before);
+
+        for point in moves {
+            assert_eq!(state.clone().try_play(point), Ok(()));
+        }
+    }
+
+    #[test]
+    fn clone_and_compare() {
+        let mut first = super::BoardState::new(9, 9);
+        play_all(&mut first, &[(2, 2), (6, 6), (2, 6)]);
+
+        // Copies are independent of the original
+        let mut copy = first.clone();
+        play_all(&mut copy, &[(6, 2)]);
+        assert_eq!(first.stone_at(point2(6, 2)), None);
+        assert!(copy != first);
+
+        // The same position reached in a different order is equal, and hashes the same
+        let mut second = super::BoardState::new(9, 9);
+        play_all(&mut second, &[(2, 6), (6, 6), (2, 2)]);
+        assert!(first == second);
+        let positions: std::collections::HashSet<_> = [first, second].into_iter().collect();
+        assert_eq!(positions.len(), 1);
+    }
+
+    #[test]
+    fn ko_detected() {
+        let mut state = super::BoardState::new(19, 19);
+
+        state.set_stone(point2(5, 5), Some(super::Player::Black));
+        state.set_stone(point2(4, 6), Some(super::Player::Black));
+        state.set_stone(point2(5, 7), Some(super::Player::Black));
+        state.set_stone(point2(6, 5), Some(super::Player::White));
+        state.set_stone(point2(7, 6), Some(super::Player::White));
+        state.set_stone(point2(6, 7), Some(super::Player::White));
+        state.set_stone(point2(5, 6), Some(super::Player::White));
+
+        assert_eq!(state.try_play(point2(6, 6)), Ok(()));
+        assert_eq!(state.stone_at(point2(5, 6)), None);
+        assert_eq!(state.ko, Some(point2(5, 6)));
+
+        // White can't retake immediately
+        assert_eq!(state.try_play(point2(5, 6)), Err(MoveError::Ko));
+
+        // But can after a ko threat is exchanged
+        assert_eq!(state.try_play(point2(15, 15)), Ok(()));
+        assert_eq!(state.ko, None);
+        assert_eq!(state.try_play(point2(15, 16)), Ok(()));
+        assert_eq!(state.try_play(point2(5, 6)), Ok(()));
+        assert_eq!(state.stone_at(point2(6, 6)), None);
+        assert_eq!(state.ko, Some(point2(6, 6)));
+    }
+
+    #[test]
+    fn snapback_is_not_ko() {
+        let mut state = super::BoardState::new(19, 19);
+
+        state.set_stone(point2(0, 0), Some(super::Player::White));
+        state.set_stone(point2(1, 0), Some(super::Player::White));
+        state.set_stone(point2(1, 1), Some(super::Player::White));
+        state.set_stone(point2(3, 0), Some(super::Player::White));
+        state.set_stone(point2(0, 1), Some(super::Player::Black));
+        state.set_stone(point2(1, 2), Some(super::Player::Black));
+        state.set_stone(point2(2, 2), Some(super::Player::Black));
+        state.set_stone(point2(3, 1), Some(super::Player::Black));
+
+        // Black throws in, and white captures the single stone with a larger group
+        assert_eq!(state.try_play(point2(2, 0)), Ok(()));
+        assert_eq!(state.try_play(point2(2, 1)), Ok(()));
+        assert_eq!(state.stone_at(point2(2, 0)), None);
+        assert_eq!(state.ko, None);
+
+        // So black can immediately take back the whole group
+        assert_eq!(state.try_play(point2(2, 0)), Ok(()));
+        assert_eq!(state.captured_white, 4);
+        assert_eq!(state.stone_at(point2(1, 1)), None);
+    }
+
+    #[test]
+    fn not_quite_ko() {
+        let mut state = super::BoardState::new(19, 19);
+
+        // Black captures one stone, but the capturing stone keeps an extra liberty
+        state.set_stone(point2(5, 5), Some(super::Player::Black));
+        state.set_stone(point2(4, 6), Some(super::Player::Black));
+        state.set_stone(point2(5, 7), Some(super::Player::Black));
+        state.set_stone(point2(6, 5), Some(super::Player::White));
+        state.set_stone(point2(6, 7), Some(super::Player::White));
+        state.set_stone(point2(5, 6), Some(super::Player::White));
+
+        assert_eq!(state.try_play(point2(6, 6)), Ok(()));
+        assert_eq!(state.stone_at(point2(5, 6)), None);
+        assert_eq!(state.ko, None);
+        assert_eq!(state.try_play(point2(5, 6)), Err(MoveError::Suicide));
+    }
+
+    // On a 3x3 board, black's seventh move recreates the position after black's first move
+    const POSITIONAL_REPEAT: [(usize, usize); 6] = [(0, 1), (1, 0), (1, 2), (1, 1), (0, 0), (0, 2)];
+
+    #[test]
+    fn simple_ko_allows_long_cycles() {
+        let mut state = super::BoardState::new(3, 3);
+        play_all(&mut state, &POSITIONAL_REPEAT);
+        assert_eq!(state.try_play(point2(0, 1)), Ok(()));
+    }
+
+    #[test]
+    fn positional_superko() {
+        let mut state = super::BoardState::new(3, 3);
+        state.rules = RuleSet::Chinese;
+        play_all(&mut state, &POSITIONAL_REPEAT);
+
+        let hash = state.position_hash();
+        let captured = (state.captured_black, state.captured_white);
+        assert_eq!(state.try_play(point2(0, 1)), Err(MoveError::Superko));
+
+        // The rejected move leaves the board untouched
+        assert_eq!(state.position_hash(), hash);
+        assert_eq!((state.captured_black, state.captured_white), captured);
+        assert_eq!(state.stone_at(point2(0, 1)), None);
+        assert_eq!(state.current_player, super::Player::Black);
+    }
+
+    #[test]
+    fn situational_superko() {
+        // The repeated position had the other player to move, which is allowed
+        let mut state = super::BoardState::new(3, 3);
+        state.rules = RuleSet::Aga;
+        play_all(&mut state, &POSITIONAL_REPEAT);
+        assert_eq!(state.try_play(point2(0, 1)), Ok(()));
+
+        // Retaking a ko recreates the position with the same player to move
+        let mut state = super::BoardState::new(19, 19);
+        state.rules = RuleSet::Aga;
+        play_all(
+            &mut state,
+            &[
+                (5, 5),
+                (6, 5),
+                (4, 6),
+                (7, 6),
+                (5, 7),
+                (6, 7),
+                (15, 15),
+                (5, 6),
+                (6, 6),
+            ],
+        );
+        state.ko = None;
+        assert_eq!(state.try_play(point2(5, 6)), Err(MoveError::Superko));
+    }
+
+    #[test]
+    fn position_hash_is_incremental() {
+        let mut state = super::BoardState::new(19, 19);
+        let empty = state.position_hash();
+
+        play_all(&mut state, &[(3, 3), (15, 15), (4, 4), (16, 16)]);
+        assert_ne!(state.position_hash(), empty);
+
+        let mut transposed = super::BoardState::new(19, 19);
+        play_all(&mut transposed, &[(4, 4), (16, 16), (3, 3), (15, 15)]);
+        assert_eq!(state.position_hash(), transposed.position_hash());
+        assert_ne!(
+            state.situation_hash(super::Player::Black),
+            state.situation_hash(super::Player::White)
+        );
+    }
+
+    #[test]
+    fn two_passes_end_the_game() {
+        let mut state = super::BoardState::new(19, 19);
+
+        assert_eq!(state.pass(), Ok(()));
+        assert_eq!(state.current_player, super::Player::White);
+        assert!(state.has_passed(super::Player::Black));
+        assert!(!state.has_passed(super::Player::White));
+
+        // Playing a stone resets the count
+        play_all(&mut state, &[(3, 3)]);
+        assert_eq!(state.consecutive_passes, 0);
+        assert!(!state.has_passed(super::Player::Black));
+
+        assert_eq!(state.pass(), Ok(()));
+        assert_eq!(state.phase, super::GamePhase::Playing);
+        assert_eq!(state.pass(), Ok(()));
+        assert_eq!(state.phase, super::GamePhase::Scoring);
+
+        assert_eq!(state.try_play(point2(4, 4)), Err(MoveError::GameOver));
+        assert_eq!(state.pass(), Err(MoveError::GameOver));
+    }
+
+    #[test]
+    fn passing_lifts_ko() {
+        let mut state = super::BoardState::new(19, 19);
+        play_all(
+            &mut state,
+            &[
+                (5, 5),
+                (6, 5),
+                (4, 6),
+                (7, 6),
+                (5, 7),
+                (6, 7),
+                (15, 15),
+                (5, 6),
+                (6, 6),
+            ],
+        );
+        assert_eq!(state.ko, Some(point2(5, 6)));
+
+        assert_eq!(state.pass(), Ok(()));
+        assert_eq!(state.ko, None);
+        assert_eq!(state.last_move, None);
+    }
+
+    #[test]
+    fn area_score() {
+        let mut state = super::BoardState::new(5, 5);
+
+        // Black walls off the left two columns, white the right two
+        for y in 0..5 {
+            state.set_stone(point2(1, y), Some(super::Player::Black));
+            state.set_stone(point2(3, y), Some(super::Player::White));
+        }
+        state.set_stone(point2(2, 2), Some(super::Player::Black));
+
+        // 6 stones + 5 territory for black, 5 + 5 + 6.5 komi for white. The rest of the middle
+        // column is neutral
+        let score = state.area_score();
+        assert_eq!(score.black, 11.0);
+        assert_eq!(score.white, 16.5);
+        assert_eq!(
+            score.result(),
+            super::GameResult::Points(super::Player::White, 5.5)
+        );
+        assert_eq!(score.result().to_string(), "W+5.5");
+    }
+
+    #[test]
+    fn neutral_points_are_not_counted() {
+        let mut state = super::BoardState::new(3, 3);
+        state.set_stone(point2(0, 0), Some(super::Player::Black));
+        state.set_stone(point2(2, 2), Some(super::Player::White));
+
+        let score = state.area_score();
+        assert_eq!(score.black, 1.0);
+        assert_eq!(score.white, 7.5);
+    }
+
+    #[test]
+    fn game_results() {
+        let mut state = super::BoardState::new(9, 9);
+        assert_eq!(
+            state.accept_score(super::Player::Black),
+            Err("The game is not being scored")
+        );
+
+        play_all(&mut state, &[(4, 4)]);
+        assert_eq!(state.pass(), Ok(()));
+        assert_eq!(state.pass(), Ok(()));
+        assert_eq!(state.accept_score(super::Player::Black), Ok(()));
+        assert_eq!(state.phase, super::GamePhase::Scoring);
+        assert_eq!(state.accept_score(super::Player::White), Ok(()));
+        assert_eq!(state.phase, super::GamePhase::Finished);
+        assert_eq!(state.result.unwrap().to_string(), "B+73.5");
+
+        let mut state = super::BoardState::new(9, 9);
+        assert_eq!(state.resign(super::Player::Black), Ok(()));
+        assert_eq!(state.result.unwrap().to_string(), "W+R");
+        assert_eq!(state.resign(super::Player::White), Err("The game is over"));
+
+        let mut state = super::BoardState::new(9, 9);
+        assert_eq!(state.time_out(super::Player::White), Ok(()));
+        assert_eq!(state.result.unwrap().to_string(), "B+T");
+        assert!(state.time_out(super::Player::Black).is_err());
+    }
+
+    // A 5x5 board split down the middle, with a dead white stone in black's area
+    fn scoring_position() -> super::BoardState {
+        let mut state = super::BoardState::new(5, 5);
+        for y in 0..5 {
+            state.set_stone(point2(2, y), Some(super::Player::Black));
+            state.set_stone(point2(3, y), Some(super::Player::White));
+        }
+        state.set_stone(point2(0, 0), Some(super::Player::White));
+        state.captured_black = 2;
+        state.captured_white = 1;
+        state.phase = super::GamePhase::Scoring;
+        state
+    }
+
+    #[test]
+    fn territory_score() {
+        let mut state = scoring_position();
+
+        // The white stone in the corner ruins black's territory until it is marked dead
+        let score = state.score();
+        assert_eq!(score.black, 1.0);
+        assert_eq!(score.white, 5.0 + 2.0 + 6.5);
+
+        assert_eq!(state.toggle_dead(point2(0, 0)), Ok(()));
+        let score = state.score();
+        assert_eq!(score.black, 10.0 + 1.0 + 1.0);
+        assert_eq!(score.white, 5.0 + 2.0 + 6.5);
+        assert_eq!(score.result().to_string(), "W+1.5");
+    }
+
+    #[test]
+    fn area_score_with_dead_stones() {
+        let mut state = scoring_position();
+        state.rules = RuleSet::Chinese;
+        assert_eq!(state.toggle_dead(point2(0, 0)), Ok(()));
+
+        let score = state.score();
+        assert_eq!(score.black, 5.0 + 10.0);
+        assert_eq!(score.white, 5.0 + 5.0 + 6.5);
+    }
+
+    #[test]
+    fn marking_dead_stones() {
+        let mut state = scoring_position();
+        assert_eq!(
+            state.toggle_dead(point2(1, 1)),
+            Err("There is no group there")
+        );
+
+        // Marking a stone marks its whole group
+        assert_eq!(state.toggle_dead(point2(3, 2)), Ok(()));
+        assert_eq!(state.dead_stones.len(), 5);
+        assert_eq!(state.toggle_dead(point2(3, 4)), Ok(()));
+        assert!(state.dead_stones.is_empty());
+
+        // Changing the dead stones needs both players to accept again
+        assert_eq!(state.accept_score(super::Player::White), Ok(()));
+        assert_eq!(state.toggle_dead(point2(0, 0)), Ok(()));
+        assert!(state.accepted_by.is_empty());
+
+        // Once accepted, dead stones become prisoners
+        assert_eq!(state.accept_score(super::Player::White), Ok(()));
+        assert_eq!(state.accept_score(super::Player::Black), Ok(()));
+        assert_eq!(state.stone_at(point2(0, 0)), None);
+        assert_eq!(state.captured_white, 2);
+        assert_eq!(state.result.unwrap().to_string(), "W+1.5");
+    }
+
+    // A black group of two stones at the edge, which fills its own last liberty at (2, 0)
+    fn suicide_position(rules: RuleSet) -> super::BoardState {
+        let mut state = super::BoardState::new(5, 5);
+        state.rules = rules;
+        state.set_stone(point2(0, 0), Some(super::Player::Black));
+        state.set_stone(point2(1, 0), Some(super::Player::Black));
+        state.set_stone(point2(0, 1), Some(super::Player::White));
+        state.set_stone(point2(1, 1), Some(super::Player::White));
+        state.set_stone(point2(2, 1), Some(super::Player::White));
+        state.set_stone(point2(3, 0), Some(super::Player::White));
+        state
+    }
+
+    #[test]
+    fn suicide_is_forbidden() {
+        let mut state = suicide_position(RuleSet::Japanese);
+        assert_eq!(state.try_play(point2(2, 0)), Err(MoveError::Suicide));
+        assert_eq!(state.stone_at(point2(0, 0)), Some(super::Player::Black));
+    }
+
+    #[test]
+    fn multi_stone_suicide() {
+        let mut state = suicide_position(RuleSet::TrompTaylor);
+        assert_eq!(state.try_play(point2(2, 0)), Ok(()));
+        assert_eq!(state.stone_at(point2(0, 0)), None);
+        assert_eq!(state.stone_at(point2(2, 0)), None);
+        assert_eq!(state.captured_black, 3);
+        assert_eq!(state.current_player, super::Player::White);
+
+        // A single stone suicide changes nothing, so is still illegal
+        play_all(&mut state, &[(4, 3), (0, 0), (3, 4)]);
+        assert_eq!(state.try_play(point2(4, 4)), Err(MoveError::Suicide));
+    }
+
+    #[test]
+    fn handicap_compensation() {
+        let mut state = super::BoardState::new(3, 3);
+        state.set_stone(point2(1, 1), Some(super::Player::Black));
+        state.handicap = 4;
+        state.komi = super::Komi::from_half_points(1);
+
+        state.rules = RuleSet::Chinese;
+        assert_eq!(state.score().white, 4.5);
+        state.rules = RuleSet::Aga;
+        assert_eq!(state.score().white, 3.5);
+        state.rules = RuleSet::Japanese;
+        assert_eq!(state.score().white, 0.5);
+    }
+
+    #[test]
+    fn fixed_handicap() {
+        let mut state = super::BoardState::new(19, 19);
+        assert_eq!(state.setup_handicap(2), Ok(()));
+        assert_eq!(state.stone_at(point2(15, 3)), Some(super::Player::Black));
+        assert_eq!(state.stone_at(point2(3, 15)), Some(super::Player::Black));
+        assert_eq!(state.current_player, super::Player::White);
+        assert_eq!(state.handicap, 2);
+        assert_eq!(state.komi.to_string(), "0.5");
+        assert_eq!(state.handicap_stones, vec![point2(15, 3), point2(3, 15)]);
+
+        let mut state = super::BoardState::new(9, 9);
+        assert_eq!(state.setup_handicap(9), Ok(()));
+        let stones = state.stones.iter().filter(|x| x.is_some()).count();
+        assert_eq!(stones, 9);
+        assert_eq!(state.stone_at(point2(4, 4)), Some(super::Player::Black));
+        assert_eq!(state.stone_at(point2(2, 4)), Some(super::Player::Black));
+    }
+
+    #[test]
+    fn handicap_point_counts() {
+        for size in [9, 13, 19] {
+            for handicap in 2..=9 {
+                let points = super::handicap_points(size, size, handicap).unwrap();
+                assert_eq!(points.len(), handicap as usize);
+            }
+        }
+
+        // 6 stones use the sides, but not the center
+        let points = super::handicap_points(13, 13, 6).unwrap();
+        assert!(points.contains(&point2(3, 6)));
+        assert!(!points.contains(&point2(6, 6)));
+    }
+
+    #[test]
+    fn one_stone_handicap() {
+        let mut state = super::BoardState::new(19, 19);
+        assert_eq!(state.setup_handicap(1), Ok(()));
+        assert_eq!(state.current_player, super::Player::Black);
+        assert_eq!(state.komi.to_string(), "0.5");
+    }
+
+    #[test]
+    fn impossible_handicap() {
+        let mut state = super::BoardState::new(5, 5);
+        assert_eq!(
+            state.setup_handicap(2),
+            Err("A handicap of 2 isn't possible on a 5x5 board".to_string())
+        );
+
+        let mut state = super::BoardState::new(10, 10);
+        assert_eq!(state.setup_handicap(4), Ok(()));
+        let mut state = super::BoardState::new(10, 10);
+        assert!(state.setup_handicap(5).is_err());
+    }
+
+    #[test]
+    fn free_handicap() {
+        let mut state = super::BoardState::new(9, 9);
+        state.rules = RuleSet::Chinese;
+        assert_eq!(state.setup_handicap(3), Ok(()));
+        assert_eq!(state.phase, super::GamePhase::Setup);
+        assert_eq!(state.komi.to_string(), "0.5");
+        assert_eq!(state.try_play(point2(4, 4)), Err(MoveError::NotYourTurn));
+
+        assert_eq!(state.toggle_handicap_stone(point2(0, 0)), Ok(()));
+        assert_eq!(state.toggle_handicap_stone(point2(1, 1)), Ok(()));
+        assert_eq!(
+            state.confirm_handicap(),
+            Err("Not all of the handicap stones are placed")
+        );
+
+        // Tapping a placed stone takes it back
+        assert_eq!(state.toggle_handicap_stone(point2(1, 1)), Ok(()));
+        assert_eq!(state.stone_at(point2(1, 1)), None);
+
+        play_handicap(&mut state, &[(2, 2), (3, 3)]);
+        assert_eq!(
+            state.toggle_handicap_stone(point2(4, 4)),
+            Err("All of the handicap stones are placed")
+        );
+
+        assert_eq!(state.confirm_handicap(), Ok(()));
+        assert_eq!(state.phase, super::GamePhase::Playing);
+        assert_eq!(state.current_player, super::Player::White);
+        assert_eq!(
+            state.handicap_stones,
+            vec![point2(0, 0), point2(2, 2), point2(3, 3)]
+        );
+        assert_eq!(state.stone_at(point2(2, 2)), Some(super::Player::Black));
+    }
+
+    fn play_handicap(state: &mut super::BoardState, stones: &[(usize, usize)]) {
+        for (x, y) in stones {
+            assert_eq!(state.toggle_handicap_stone(point2(*x, *y)), Ok(()));
+        }
+    }
+
+    #[test]
+    fn komi() {
+        for text in ["0", "0.5", "6.5", "7", "-0.5", "-7.5", "100"] {
+            assert_eq!(super::Komi::parse(text).unwrap().to_string(), text);
+        }
+        assert_eq!(super::Komi::parse("7.50").unwrap().to_string(), "7.5");
+        assert_eq!(super::Komi::parse("6.25"), None);
+        assert_eq!(super::Komi::parse("Auto"), None);
+        assert_eq!(super::Komi::parse("-0.5").unwrap().points(), -0.5);
+    }
+
+    #[test]
+    fn reverse_komi() {
+        let mut state = super::BoardState::new(3, 3);
+        state.komi = super::Komi::parse("-3").unwrap();
+        state.set_stone(point2(1, 1), Some(super::Player::White));
+
+        let score = state.area_score();
+        assert_eq!(score.white, 6.0);
+        assert_eq!(score.result().to_string(), "W+6");
+    }
+
+    #[test]
+    fn board_sizes() {
+        assert_eq!(super::parse_board_size("19x19"), Ok((19, 19)));
+        assert_eq!(super::parse_board_size("9x13"), Ok((9, 13)));
+        assert_eq!(super::parse_board_size("7"), Ok((7, 7)));
+        assert_eq!(super::parse_board_size("2x25"), Ok((2, 25)));
+        assert!(super::parse_board_size("1x1").is_err());
+        assert!(super::parse_board_size("26x26").is_err());
+        assert!(super::parse_board_size("19x").is_err());
+    }
+
+    #[test]
+    fn rectangular_board() {
+        let mut state = super::BoardState::new(2, 5);
+
+        // Black's stone in the bottom left corner is captured against both edges
+        play_all(&mut state, &[(0, 4), (0, 3), (1, 0), (1, 4)]);
+        assert_eq!(state.stone_at(point2(0, 4)), None);
+        assert_eq!(state.captured_black, 1);
+
+        let points = super::handicap_points(9, 13, 4).unwrap();
+        assert!(points.contains(&point2(6, 3)));
+        assert!(points.contains(&point2(2, 9)));
+        assert_eq!(super::handicap_points(9, 13, 5).unwrap()[4], point2(4, 6));
+        assert_eq!(super::handicap_points(9, 12, 5), None);
     }
 }
@@ -0,0 +1,66 @@
+use crate::text;
+use crate::text::TextAlignment;
+use crate::ui::{UiComponent, UiController};
+use cgmath::{Point2, Vector2};
+use libremarkable::framebuffer::common::{
+    color, display_temp, dither_mode, mxcfb_rect, waveform_mode, DRAWING_QUANT_BIT,
+};
+use libremarkable::framebuffer::{FramebufferDraw, FramebufferRefresh, PartialRefreshMode};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// A line of text which is derived from the scene's state
+pub struct LabelUi<State> {
+    position: Point2<i32>,
+    size: Vector2<u32>,
+    text_size: i32,
+    text: Box<dyn Fn(&State) -> String>,
+}
+
+impl<State> LabelUi<State> {
+    pub fn new(
+        position: Point2<i32>,
+        size: Vector2<u32>,
+        text: Box<dyn Fn(&State) -> String>,
+    ) -> LabelUi<State> {
+        LabelUi {
+            position,
+            size,
+            text_size: 18,
+            text,
+        }
+    }
+}
+
+impl<State> UiComponent<State> for LabelUi<State> {
+    fn draw(&self, ui: Rc<RefCell<&mut UiController>>, state: &State) {
+        let fb = ui.borrow_mut().context.get_framebuffer_ref();
+
+        fb.fill_rect(self.position, self.size, color::WHITE);
+        text::draw_text(
+            fb,
+            self.position,
+            TextAlignment::Left,
+            self.text_size,
+            color::BLACK,
+            &(self.text)(state),
+        );
+
+        let refresh_rect = mxcfb_rect {
+            top: self.position.y as u32,
+            left: self.position.x as u32,
+            width: self.size.x,
+            height: self.size.y,
+        };
+
+        fb.partial_refresh(
+            &refresh_rect,
+            PartialRefreshMode::Async,
+            waveform_mode::WAVEFORM_MODE_GC16_FAST,
+            display_temp::TEMP_USE_REMARKABLE_DRAW,
+            dither_mode::EPDC_FLAG_USE_DITHERING_PASSTHROUGH,
+            DRAWING_QUANT_BIT,
+            false,
+        );
+    }
+}
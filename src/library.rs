@@ -0,0 +1,155 @@
+use crate::game_tree::GameTree;
+use crate::go::BoardState;
+use crate::sgf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// What the library shows about a game without opening it
+pub struct SavedGame {
+    pub path: PathBuf,
+    pub date: String,
+    pub black: String,
+    pub white: String,
+    pub result: String,
+    pub width: usize,
+    pub height: usize,
+    // The position at the end of the main line, drawn as a thumbnail
+    pub final_position: BoardState,
+}
+
+impl SavedGame {
+    fn read(path: PathBuf) -> Result<SavedGame, String> {
+        let text = fs::read_to_string(&path).map_err(|error| error.to_string())?;
+        let mut tree = sgf::read(&text)?;
+        while tree.go_to_child(0) {}
+
+        let root = tree.root();
+        let property =
+            |name: &str, default: &str| root.property(name).unwrap_or(default).to_string();
+        Ok(SavedGame {
+            date: property("DT", ""),
+            black: property("PB", "Black"),
+            white: property("PW", "White"),
+            result: property("RE", "No result"),
+            width: tree.root_state().width,
+            height: tree.root_state().height,
+            final_position: tree.current_state().clone(),
+            path,
+        })
+    }
+}
+
+fn directory(data_directory: &Path) -> PathBuf {
+    return data_directory.join("games");
+}
+
+// Days since 1970 to a year, month and day, from Howard Hinnant's civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    return (year, month, day);
+}
+
+// The date as SGF's DT property writes it, and the time, in UTC
+fn date_and_time(time: SystemTime) -> (String, String) {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let second_of_day = seconds.rem_euclid(86400);
+    return (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!(
+            "{:02}{:02}{:02}",
+            second_of_day / 3600,
+            second_of_day / 60 % 60,
+            second_of_day % 60
+        ),
+    );
+}
+
+// Adds a finished game to the library, dated today unless its record already has a date. Files
+// are named after when they were saved, so they sort from oldest to newest
+pub fn add(tree: &mut GameTree, data_directory: &Path) -> io::Result<()> {
+    let (date, time) = date_and_time(SystemTime::now());
+    if tree.root().property("DT").is_none() {
+        tree.root_mut().set_property("DT", &date);
+    }
+
+    let directory = directory(data_directory);
+    fs::create_dir_all(&directory)?;
+    let path = directory.join(format!("{}-{}.sgf", date, time));
+    return fs::write(path, sgf::write(tree));
+}
+
+// The games in the library, newest first. Files which can't be read are left out
+pub fn list(data_directory: &Path) -> Vec<SavedGame> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(directory(data_directory)) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "sgf"))
+            .collect(),
+        Err(_) => vec![],
+    };
+    paths.sort();
+    paths.reverse();
+
+    return paths
+        .into_iter()
+        .filter_map(|path| SavedGame::read(path).ok())
+        .collect();
+}
+
+pub fn open(game: &SavedGame) -> Result<GameTree, String> {
+    let text = fs::read_to_string(&game.path)
+        .map_err(|error| format!("The game couldn't be opened: {}", error))?;
+    return sgf::read(&text);
+}
+
+pub fn delete(game: &SavedGame) -> io::Result<()> {
+    return fs::remove_file(&game.path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SavedGame;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn summary() {
+        let game = SavedGame::read(PathBuf::from("tests/fixtures/ogs.sgf")).unwrap();
+        assert_eq!((game.black.as_str(), game.white.as_str()), ("alice", "bob"));
+        assert_eq!(game.result, "W+R");
+        assert_eq!(game.date, "2023-03-14");
+        assert_eq!((game.width, game.height), (9, 9));
+        assert!(game.final_position.stone_at(cgmath::point2(4, 4)).is_some());
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(super::civil_from_days(0), (1970, 1, 1));
+        assert_eq!(super::civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(super::civil_from_days(11016), (2000, 2, 29));
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_792_329_330);
+        assert_eq!(
+            super::date_and_time(time),
+            (String::from("2026-10-18"), String::from("131530"))
+        );
+    }
+}
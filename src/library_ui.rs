@@ -0,0 +1,255 @@
+use crate::go::{BoardState, Player};
+use crate::library;
+use crate::library::SavedGame;
+use crate::text::TextAlignment;
+use crate::ui::{UiComponent, UiController, UiEvent};
+use crate::{drawing, text, ui};
+use cgmath::{point2, vec2, Point2, Vector2};
+use libremarkable::appctx::ApplicationContext;
+use libremarkable::framebuffer::common::{
+    color, display_temp, dither_mode, mxcfb_rect, waveform_mode, DRAWING_QUANT_BIT,
+};
+use libremarkable::framebuffer::core::Framebuffer;
+use libremarkable::framebuffer::{FramebufferDraw, FramebufferRefresh, PartialRefreshMode};
+use libremarkable::input::{InputEvent, MultitouchEvent};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const GAMES_PER_PAGE: usize = 5;
+
+pub struct LibraryState {
+    // Every saved game, newest first
+    pub games: Vec<SavedGame>,
+    pub page: usize,
+    // A game whose delete button has been pressed once, and is deleted if it's pressed again
+    pub confirm_delete: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl LibraryState {
+    pub fn page_count(&self) -> usize {
+        return self.games.len().div_ceil(GAMES_PER_PAGE).max(1);
+    }
+}
+
+// One page of saved games, each with a thumbnail of its final position. Pressing a game opens it
+pub struct LibraryUi {
+    position: Point2<i32>,
+    row_size: Vector2<u32>,
+    thumbnail_size: u32,
+    delete_size: Vector2<u32>,
+    text_size: i32,
+    open: Box<dyn Fn(Rc<RefCell<&mut UiController>>, &mut LibraryState, usize)>,
+}
+
+impl LibraryUi {
+    pub fn new(
+        ctx: &ApplicationContext,
+        top: i32,
+        open: Box<dyn Fn(Rc<RefCell<&mut UiController>>, &mut LibraryState, usize)>,
+    ) -> LibraryUi {
+        let border = 100;
+        let (_, screen_width) = ctx.get_dimensions();
+
+        LibraryUi {
+            position: point2(border, top),
+            row_size: vec2(screen_width - border as u32 * 2, 220),
+            thumbnail_size: 180,
+            delete_size: vec2(200, 80),
+            text_size: 18,
+            open,
+        }
+    }
+
+    fn row_start(&self, row: usize) -> Point2<i32> {
+        self.position + vec2(0, (self.row_size.y as usize * row) as i32)
+    }
+
+    fn delete_start(&self, row: usize) -> Point2<i32> {
+        let offset = vec2(
+            self.row_size.x as i32 - self.delete_size.x as i32,
+            (self.row_size.y - self.delete_size.y) as i32 / 2,
+        );
+        self.row_start(row) + offset
+    }
+
+    fn draw_game(&self, fb: &mut Framebuffer, row: usize, game: &SavedGame, confirm: bool) {
+        let start = self.row_start(row);
+        let thumbnail_start = start + vec2(0, (self.row_size.y - self.thumbnail_size) as i32 / 2);
+        draw_thumbnail(
+            fb,
+            thumbnail_start,
+            self.thumbnail_size,
+            &game.final_position,
+        );
+
+        let text_start = start + vec2(self.thumbnail_size as i32 + 40, 40);
+        let lines = [
+            format!("{} vs {}", game.black, game.white),
+            format!("{}x{}   {}", game.width, game.height, game.result),
+            game.date.clone(),
+        ];
+        for (line, text) in lines.iter().enumerate() {
+            text::draw_text(
+                fb,
+                text_start + vec2(0, line as i32 * 50),
+                TextAlignment::Left,
+                self.text_size,
+                color::BLACK,
+                text,
+            );
+        }
+
+        let delete_start = self.delete_start(row);
+        drawing::draw_rect(fb, delete_start, self.delete_size, 2);
+        text::draw_text(
+            fb,
+            delete_start + vec2(self.delete_size.x as i32 / 2, 25),
+            TextAlignment::Centered,
+            self.text_size,
+            color::BLACK,
+            if confirm { "Confirm" } else { "Delete" },
+        );
+
+        drawing::draw_horizontal_line(
+            fb,
+            start + vec2(0, self.row_size.y as i32 - 1),
+            self.row_size.x,
+        );
+    }
+}
+
+// A small board with no star points, where each stone fills its square
+fn draw_thumbnail(fb: &mut Framebuffer, start: Point2<i32>, size: u32, board: &BoardState) {
+    let square = (size as usize / board.width.max(board.height)) as i32;
+    let offset = vec2(square / 2, square / 2);
+    let board_size = vec2(square * board.width as i32, square * board.height as i32);
+    drawing::draw_rect(fb, start, board_size.cast().unwrap(), 1);
+
+    for x in 0..board.width {
+        let line_start = start + offset + vec2(x as i32 * square, 0);
+        drawing::draw_vertical_line(fb, line_start, (board_size.y - square) as u32);
+    }
+    for y in 0..board.height {
+        let line_start = start + offset + vec2(0, y as i32 * square);
+        drawing::draw_horizontal_line(fb, line_start, (board_size.x - square) as u32);
+    }
+
+    let radius = (square as u32 / 2).max(1);
+    for x in 0..board.width {
+        for y in 0..board.height {
+            let center = start + offset + vec2(x as i32 * square, y as i32 * square);
+            match board.stone_at(point2(x, y)) {
+                Some(Player::Black) => {
+                    fb.fill_circle(center, radius, color::BLACK);
+                }
+                Some(Player::White) => {
+                    fb.fill_circle(center, radius, color::WHITE);
+                    fb.draw_circle(center, radius, color::BLACK);
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+impl UiComponent<LibraryState> for LibraryUi {
+    fn handle_event(
+        &mut self,
+        ui: Rc<RefCell<&mut UiController>>,
+        state: &mut LibraryState,
+        event: &UiEvent,
+    ) {
+        if let UiEvent::Input(InputEvent::MultitouchEvent { event, .. }) = event {
+            if let MultitouchEvent::Press { finger } = event {
+                let position = finger.pos.cast::<i32>().unwrap();
+                let list_end = self.row_start(GAMES_PER_PAGE);
+                if position.x < self.position.x
+                    || position.x >= self.position.x + self.row_size.x as i32
+                    || position.y < self.position.y
+                    || position.y >= list_end.y
+                {
+                    return;
+                }
+
+                let row = ((position.y - self.position.y) / self.row_size.y as i32) as usize;
+                let index = state.page * GAMES_PER_PAGE + row;
+                if index >= state.games.len() {
+                    return;
+                }
+
+                let delete_start = self.delete_start(row);
+                let on_delete = position.x >= delete_start.x
+                    && position.y >= delete_start.y
+                    && position.y < delete_start.y + self.delete_size.y as i32;
+
+                state.error = None;
+                if !on_delete {
+                    state.confirm_delete = None;
+                    (self.open)(ui, state, index);
+                } else if state.confirm_delete == Some(index) {
+                    state.confirm_delete = None;
+                    match library::delete(&state.games[index]) {
+                        Ok(()) => {
+                            state.games.remove(index);
+                            state.page = state.page.min(state.page_count() - 1);
+                        }
+                        Err(error) => {
+                            state.error = Some(format!("Couldn't delete the game: {}", error))
+                        }
+                    }
+                } else {
+                    state.confirm_delete = Some(index);
+                }
+                ui::post_redraw();
+            }
+        }
+    }
+
+    fn draw(&self, ui: Rc<RefCell<&mut UiController>>, state: &LibraryState) {
+        let fb = ui.borrow_mut().context.get_framebuffer_ref();
+
+        let list_size = vec2(self.row_size.x, self.row_size.y * GAMES_PER_PAGE as u32);
+        fb.fill_rect(self.position, list_size, color::WHITE);
+
+        if state.games.is_empty() {
+            text::draw_text(
+                fb,
+                self.position + vec2(self.row_size.x as i32 / 2, 100),
+                TextAlignment::Centered,
+                self.text_size,
+                color::BLACK,
+                "Finished games are kept here",
+            );
+        }
+
+        let first = state.page * GAMES_PER_PAGE;
+        for (row, game) in state
+            .games
+            .iter()
+            .skip(first)
+            .take(GAMES_PER_PAGE)
+            .enumerate()
+        {
+            let confirm = state.confirm_delete == Some(first + row);
+            self.draw_game(fb, row, game, confirm);
+        }
+
+        let refresh_rect = mxcfb_rect {
+            top: self.position.y as u32,
+            left: self.position.x as u32,
+            width: list_size.x,
+            height: list_size.y,
+        };
+
+        fb.partial_refresh(
+            &refresh_rect,
+            PartialRefreshMode::Async,
+            waveform_mode::WAVEFORM_MODE_GC16_FAST,
+            display_temp::TEMP_USE_REMARKABLE_DRAW,
+            dither_mode::EPDC_FLAG_USE_DITHERING_PASSTHROUGH,
+            DRAWING_QUANT_BIT,
+            false,
+        );
+    }
+}
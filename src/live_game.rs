@@ -0,0 +1,140 @@
+use crate::autosave;
+use crate::clock;
+use crate::clock::GameClock;
+use crate::game_tree::GameTree;
+use crate::go::{BoardState, GamePhase, MoveError, Player};
+use crate::settings::GameSettings;
+use cgmath::Point2;
+use std::io;
+use std::path::PathBuf;
+
+// A game being played, which each mode's controller wraps. Every change is saved as it's made,
+// and the clocks are kept running for whoever is to move
+pub struct LiveGame {
+    pub tree: GameTree,
+    pub clock: Option<GameClock>,
+    // Why the last move or pass was refused, until one is made
+    pub move_error: Option<MoveError>,
+    // Why the game couldn't be saved, until it next saves successfully
+    pub save_error: Option<io::Error>,
+    // Where the game is saved as it's played, and where the library it ends up in is kept
+    directory: PathBuf,
+}
+
+impl LiveGame {
+    // The menu's settings are kept on the record, so the game can be resumed the same way
+    pub fn start(settings: &GameSettings, state: BoardState, directory: PathBuf) -> LiveGame {
+        let mut tree = GameTree::new(state);
+        autosave::record_settings(&mut tree, settings);
+        return LiveGame::resume(tree, directory);
+    }
+
+    // Carries on a saved game from wherever its cursor is, with the time each player had left
+    pub fn resume(tree: GameTree, directory: PathBuf) -> LiveGame {
+        LiveGame {
+            clock: clock::start(&autosave::settings(&tree), &tree),
+            tree,
+            move_error: None,
+            save_error: None,
+            directory,
+        }
+    }
+
+    pub fn save(&mut self) {
+        self.save_error = autosave::save(&mut self.tree, &self.directory).err();
+    }
+
+    pub fn play(&mut self, point: Point2<usize>) -> Result<(), MoveError> {
+        self.check_time();
+        let result = self.tree.play(point);
+        self.move_error = result.clone().err();
+        if result.is_ok() {
+            clock::after_move(&mut self.clock, &mut self.tree);
+            self.save();
+        }
+        return result;
+    }
+
+    pub fn pass(&mut self) -> Result<(), MoveError> {
+        self.check_time();
+        let result = self.tree.pass();
+        self.move_error = result.clone().err();
+        if result.is_ok() {
+            clock::after_move(&mut self.clock, &mut self.tree);
+            self.save();
+        }
+        return result;
+    }
+
+    pub fn resign(&mut self, player: Player) -> Result<(), &'static str> {
+        self.tree.current_state_mut().resign(player)?;
+        self.changed();
+        return Ok(());
+    }
+
+    // Ends the game if the player to move has run out of time
+    pub fn check_time(&mut self) {
+        if clock::check_time(&mut self.clock, &mut self.tree) {
+            self.save();
+        }
+    }
+
+    // Nothing is saved until the handicap stones are confirmed and the game starts
+    pub fn toggle_handicap_stone(&mut self, point: Point2<usize>) -> Result<(), &'static str> {
+        self.tree.toggle_handicap_stone(point)
+    }
+
+    pub fn confirm_handicap(&mut self) -> Result<(), &'static str> {
+        self.tree.current_state_mut().confirm_handicap()?;
+        self.changed();
+        return Ok(());
+    }
+
+    pub fn toggle_dead(&mut self, point: Point2<usize>) -> Result<(), &'static str> {
+        self.tree.toggle_dead(point)?;
+        self.save();
+        return Ok(());
+    }
+
+    pub fn accept_score(&mut self, player: Player) -> Result<(), &'static str> {
+        self.tree.accept_score(player)?;
+        self.changed();
+        return Ok(());
+    }
+
+    // Takes back a move, and carries on taking them back until reaching a position where `stop` is
+    // true, or the start of the game
+    pub fn undo(&mut self, stop: impl Fn(&BoardState) -> bool) -> Result<(), &'static str> {
+        if self.tree.current_state().phase == GamePhase::Finished {
+            return Err("The game is over");
+        }
+        if !self.tree.undo() {
+            return Err("There are no moves to undo");
+        }
+        while !stop(self.tree.current_state()) && self.tree.undo() {}
+
+        self.move_error = None;
+        self.changed();
+        return Ok(());
+    }
+
+    pub fn redo(&mut self, stop: impl Fn(&BoardState) -> bool) -> Result<(), &'static str> {
+        if self.tree.current_state().phase == GamePhase::Finished {
+            return Err("The game is over");
+        }
+        if !self.tree.redo() {
+            return Err("There are no moves to redo");
+        }
+        while !stop(self.tree.current_state()) && self.tree.redo() {}
+
+        self.move_error = None;
+        self.changed();
+        return Ok(());
+    }
+
+    // After a change to the game which isn't a move, the clocks follow it and it's saved
+    fn changed(&mut self) {
+        clock::follow(&mut self.clock, &self.tree);
+        self.save();
+    }
+}
@@ -1,22 +1,42 @@
+use crate::bot_worker::Notify;
+use crate::button_ui::ButtonUi;
 use crate::game_controller::{ControllerOption, GameController};
-use crate::go::Player;
+use crate::go::{GamePhase, Player};
+use crate::label_ui::LabelUi;
+use crate::library_ui::LibraryState;
+use crate::settings::{GameSettings, Mode};
 use crate::ui::UiController;
 use crate::utility::vec_of_strings;
+use cgmath::{point2, vec2, Point2, Vector2};
 use libremarkable::appctx::ApplicationContext;
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
+use std::time::Duration;
 
+mod autosave;
 mod board_ui;
+mod bot;
+mod bot_worker;
+mod button_ui;
 mod cgmath_extensions;
+mod clock;
 mod drawing;
 mod game_controller;
+mod game_tree;
 mod go;
+mod label_ui;
+mod library;
+mod library_ui;
+mod live_game;
 mod ogs_controller;
 mod one_player_controller;
 mod option_ui;
 mod player_ui;
 mod quit_ui;
+mod review_controller;
+mod rules;
+mod settings;
+mod sgf;
 mod text;
 mod two_player_controller;
 mod ui;
@@ -25,42 +45,49 @@ mod utility;
 fn main() {
     let ctx = ApplicationContext::default();
 
-    let mut initial_settings : HashMap<String, String> = HashMap::new();
-    initial_settings.insert("Mode".to_string(), "2-Player".to_string());
-    initial_settings.insert("Board Size".to_string(), "19x19".to_string());
-    initial_settings.insert("Difficulty".to_string(), "Medium".to_string());
-    initial_settings.insert("Handicap".to_string(), "0".to_string());
-    initial_settings.insert("Clock".to_string(), "Rapid".to_string());
-    initial_settings.insert("".to_string(), "Play".to_string()); // Dummy for play
-
-    let menu = create_menu_scene(
-        &ctx,
-        two_player_controller::options(),
-        initial_settings,
-    );
+    let initial_state = MenuState {
+        settings: settings::load(),
+        error: None,
+    };
+    let menu = create_menu_scene(&ctx, initial_state);
     let mut controller = UiController::new(ctx, Rc::from(RefCell::new(menu)));
     let ui = Rc::from(RefCell::new(&mut controller));
     UiController::start(ui);
 }
 
-fn create_menu_scene(
-    ctx: &ApplicationContext,
-    options: Vec<ControllerOption>,
-    state: HashMap<String, String>
-) -> ui::Scene<HashMap<String, String>> {
+// The menu's selections, and why a game couldn't be started from them
+#[derive(Clone)]
+struct MenuState {
+    settings: GameSettings,
+    error: Option<String>,
+}
+
+// Remembers the selections for next time, or says why they couldn't be
+fn save_settings(state: &mut MenuState) {
+    if let Err(error) = settings::save(&state.settings) {
+        state.error = Some(format!("Couldn't save the settings: {}", error));
+    }
+}
+
+fn create_menu_scene(ctx: &ApplicationContext, state: MenuState) -> ui::Scene<MenuState> {
+    let options = controller_options(state.settings.mode);
     let mut menu = ui::Scene::new(state);
 
     menu.add(option_ui::OptionUi::new(
         ctx,
         400i32,
         "Mode".to_string(),
-        vec_of_strings!["1-Player", "2-Player", "OGS"],
+        Mode::ALL
+            .iter()
+            .map(|mode| mode.name().to_string())
+            .collect(),
+        Box::new(|state: &MenuState| state.settings.mode.name().to_string()),
         Box::new(
-            |ui: Rc<RefCell<&mut UiController>>,
-             _state: &mut HashMap<String, String>,
-             value: &String| {
-                let options = controller_options_from_name(&*value);
-                let scene = create_menu_scene(&ui.borrow_mut().context, options, _state.clone());
+            |ui: Rc<RefCell<&mut UiController>>, state: &mut MenuState, value: &String| {
+                state.error = None;
+                let _ = state.settings.set("Mode", value);
+                save_settings(state);
+                let scene = create_menu_scene(&ui.borrow_mut().context, state.clone());
                 UiController::change_scene(ui, Rc::from(RefCell::new(scene)), false);
             },
         ),
@@ -68,70 +95,496 @@ fn create_menu_scene(
 
     let mut position = 600i32;
     for option in options {
+        let name = option.name.clone();
         menu.add(option_ui::OptionUi::new(
             ctx,
             position,
-            option.name,
+            option.name.clone(),
             option.values,
+            Box::new(move |state: &MenuState| state.settings.get(&name).unwrap_or_default()),
             Box::new(
                 move |_ui: Rc<RefCell<&mut UiController>>,
-                      state: &mut HashMap<String, String>,
+                      state: &mut MenuState,
                       value: &String| {
+                    state.error = state.settings.set(&option.name, value).err();
+                    save_settings(state);
                 },
             ),
         ));
 
-        position += 200;
+        position += 160;
     }
 
+    // A game which was still going when the app was closed can be picked up again
+    let start_options = match autosave::exists(&autosave::data_directory()) {
+        true => vec_of_strings!["Play", "Resume game", "Library"],
+        false => vec_of_strings!["Play", "Library"],
+    };
     menu.add(option_ui::OptionUi::new(
         ctx,
         1400i32,
         "".to_string(),
-        vec_of_strings!["Play"],
+        start_options,
+        Box::new(|_state: &MenuState| "Play".to_string()),
         Box::new(
-            |ui: Rc<RefCell<&mut UiController>>,
-             state: &mut HashMap<String, String>,
-             _value: &String| {
-                let game_controller =
-                    controller_from_name(&*state.get("Mode").unwrap(), state.clone());
-                let scene = create_game_scene(&ui.borrow_mut().context, game_controller);
-                UiController::change_scene(ui.clone(), Rc::from(RefCell::new(scene)), true);
+            |ui: Rc<RefCell<&mut UiController>>, state: &mut MenuState, value: &String| {
+                if value == "Library" {
+                    let scene = create_library_scene(&ui.borrow_mut().context, 0);
+                    UiController::change_scene(ui.clone(), Rc::from(RefCell::new(scene)), true);
+                    return;
+                }
+
+                let waker = ui.borrow_mut().waker();
+                let game_controller = match value.as_str() {
+                    "Resume game" => resume_game(waker),
+                    _ => new_game(&state.settings, waker),
+                };
+                match game_controller {
+                    Ok(game_controller) => show_game(ui, game_controller),
+                    Err(message) => {
+                        state.error = Some(message);
+                    }
+                }
             },
         ),
     ));
 
+    menu.add(LabelUi::new(
+        point2(250, 1530),
+        vec2(904u32, 50u32),
+        Box::new(|state: &MenuState| state.error.clone().unwrap_or_default()),
+    ));
+
     return menu;
 }
 
+// Switches to a game, with the clocks ticking if it has them
+fn show_game(ui: Rc<RefCell<&mut UiController>>, game_controller: Box<dyn GameController>) {
+    let timed = game_controller.clock().is_some();
+    let scene = create_game_scene(&ui.borrow_mut().context, game_controller);
+    UiController::change_scene(ui.clone(), Rc::from(RefCell::new(scene)), true);
+    if timed {
+        ui.borrow_mut()
+            .schedule_repeating(player_ui::CLOCK_TIMER, Duration::from_secs(1));
+    }
+}
+
 fn create_game_scene(
     ctx: &ApplicationContext,
     game_controller: Box<dyn GameController>,
 ) -> ui::Scene<Box<dyn GameController>> {
-    let size = game_controller.current_game_state().size;
+    let board = game_controller.current_game_state();
+    let (width, height) = (board.width, board.height);
     let mut gameplay = ui::Scene::new(game_controller);
-    gameplay.add(board_ui::BoardUi::new(ctx, size));
+    gameplay.add(board_ui::BoardUi::new(ctx, width, height));
     gameplay.add(player_ui::PlayerUi::new(ctx, "White", true, Player::White));
     gameplay.add(player_ui::PlayerUi::new(ctx, "Black", false, Player::Black));
     gameplay.add(quit_ui::QuitUi::new(ctx));
 
+    // Game controls sit between the board and the player bars
+    let (screen_height, screen_width) = ctx.get_dimensions();
+    let button_size = vec2(240u32, 80u32);
+    let button_top = screen_height as i32 - 224;
+    for button in game_buttons(screen_width as i32, screen_height as i32, button_size) {
+        let press = button.press;
+        gameplay.add(
+            ButtonUi::new(
+                button.position,
+                button_size,
+                button.label,
+                Box::new(
+                    move |_ui: Rc<RefCell<&mut UiController>>,
+                          state: &mut Box<dyn GameController>| press(state),
+                ),
+            )
+            .visible_when(Box::new(move |state: &Box<dyn GameController>| {
+                button.shown(state.as_ref())
+            })),
+        );
+    }
+
+    gameplay.add(LabelUi::new(
+        point2(50, button_top + 30),
+        vec2(500u32, 50u32),
+        Box::new(|state: &Box<dyn GameController>| {
+            if let Some(error) = state.save_error() {
+                return format!("Couldn't save the game: {}", error);
+            }
+            let game_state = state.current_game_state();
+            match (game_state.phase, game_state.result) {
+                (GamePhase::Setup, _) => format!(
+                    "Placed {} of {} handicap stones",
+                    game_state.handicap_stones.len(),
+                    game_state.handicap
+                ),
+                (GamePhase::Scoring, _) => format!("Score  {}", game_state.score().result()),
+                (GamePhase::Finished, Some(result)) => format!("Result  {}", result),
+                _ => match (state.move_error(), state.pending_request()) {
+                    (Some(error), _) => error.to_string(),
+                    (None, Some(request)) => request.to_string(),
+                    (None, None) => String::new(),
+                },
+            }
+        }),
+    ));
+
     return gameplay;
 }
 
-fn controller_from_name(name: &str, options: HashMap<String, String>) -> Box<dyn GameController> {
-    match name {
-        "1-Player" => Box::new(one_player_controller::OnePlayerController::new(options)),
-        "2-Player" => Box::new(two_player_controller::TwoPlayerController::new(options)),
-        "OGS" => Box::new(ogs_controller::OgsController::new(options)),
-        _ => panic!("Unknown game type"),
+// One of the game controls, and the phases it's shown in
+#[derive(Clone, Copy)]
+struct GameButton {
+    label: &'static str,
+    position: Point2<i32>,
+    phases: &'static [GamePhase],
+    press: fn(&mut Box<dyn GameController>),
+    // Each player accepts the count with a button of their own, which goes once they have
+    player: Option<Player>,
+}
+
+impl GameButton {
+    fn shown(&self, state: &dyn GameController) -> bool {
+        self.phases.contains(&state.game_phase())
+            && self
+                .player
+                .is_none_or(|player| !state.current_game_state().accepted_by.contains(&player))
+    }
+}
+
+// Where the game controls go. Black's are below the board, and taking moves back is done from
+// above it, out of the way of the others. Buttons only share a spot when they're never shown at the
+// same time
+fn game_buttons(screen_width: i32, screen_height: i32, size: Vector2<u32>) -> Vec<GameButton> {
+    let (top, bottom) = (144, screen_height - 224);
+    let from_left = |index: i32| 50 + index * (size.x as i32 + 40);
+    let from_right = |index: i32| screen_width - 50 - size.x as i32 - index * (size.x as i32 + 40);
+    vec![
+        GameButton {
+            label: "Pass",
+            position: point2(from_right(0), bottom),
+            phases: &[GamePhase::Playing],
+            press: |state| {
+                let _ = state.pass();
+            },
+            player: None,
+        },
+        GameButton {
+            label: "Resign",
+            position: point2(from_right(1), bottom),
+            phases: &[GamePhase::Playing],
+            press: |state| {
+                let _ = state.resign();
+            },
+            player: None,
+        },
+        GameButton {
+            label: "Done",
+            position: point2(from_right(0), bottom),
+            phases: &[GamePhase::Setup],
+            press: |state| {
+                let _ = state.confirm_handicap();
+            },
+            player: None,
+        },
+        GameButton {
+            label: "Accept",
+            position: point2(from_right(2), bottom),
+            phases: &[GamePhase::Scoring],
+            press: |state| {
+                let _ = state.accept_score(Player::Black);
+            },
+            player: Some(Player::Black),
+        },
+        GameButton {
+            label: "Undo",
+            position: point2(from_left(0), top),
+            phases: &[GamePhase::Playing, GamePhase::Scoring],
+            press: undo,
+            player: None,
+        },
+        GameButton {
+            label: "Redo",
+            position: point2(from_left(1), top),
+            phases: &[GamePhase::Playing, GamePhase::Scoring],
+            press: redo,
+            player: None,
+        },
+        GameButton {
+            label: "Accept",
+            position: point2(from_right(0), top),
+            phases: &[GamePhase::Scoring],
+            press: |state| {
+                let _ = state.accept_score(Player::White);
+            },
+            player: Some(Player::White),
+        },
+    ]
+}
+
+// Refused undos and redos are left as they are, the board simply doesn't change
+fn undo(state: &mut Box<dyn GameController>) {
+    let _ = state.undo();
+}
+
+fn redo(state: &mut Box<dyn GameController>) {
+    let _ = state.redo();
+}
+
+// Steps all the way back to the start, or forward to the end of the line being shown
+fn first(state: &mut Box<dyn GameController>) {
+    while state.undo().is_ok() {}
+}
+
+fn last(state: &mut Box<dyn GameController>) {
+    while state.redo().is_ok() {}
+}
+
+fn create_library_scene(ctx: &ApplicationContext, page: usize) -> ui::Scene<LibraryState> {
+    let mut state = LibraryState {
+        games: library::list(&autosave::data_directory()),
+        page: 0,
+        confirm_delete: None,
+        error: None,
+    };
+    state.page = page.min(state.page_count() - 1);
+    let mut scene = ui::Scene::new(state);
+    scene.add(quit_ui::QuitUi::new(ctx));
+
+    scene.add(LabelUi::new(
+        point2(100, 100),
+        vec2(600u32, 50u32),
+        Box::new(|_state: &LibraryState| "Saved games".to_string()),
+    ));
+
+    scene.add(library_ui::LibraryUi::new(
+        ctx,
+        200,
+        Box::new(
+            |ui: Rc<RefCell<&mut UiController>>, state: &mut LibraryState, index: usize| {
+                match library::open(&state.games[index]) {
+                    Ok(tree) => {
+                        let game_controller =
+                            Box::new(review_controller::ReviewController::new(tree));
+                        let scene = create_review_scene(
+                            &ui.borrow_mut().context,
+                            game_controller,
+                            state.page,
+                        );
+                        UiController::change_scene(ui.clone(), Rc::from(RefCell::new(scene)), true);
+                    }
+                    Err(message) => state.error = Some(message),
+                }
+            },
+        ),
+    ));
+
+    // Paging and the way back sit below the list
+    let (screen_height, screen_width) = ctx.get_dimensions();
+    let button_size = vec2(240u32, 80u32);
+    let button_top = screen_height as i32 - 300;
+
+    scene.add(
+        ButtonUi::new(
+            point2(100, button_top),
+            button_size,
+            "Previous",
+            Box::new(
+                |_ui: Rc<RefCell<&mut UiController>>, state: &mut LibraryState| {
+                    state.page -= 1;
+                    state.confirm_delete = None;
+                },
+            ),
+        )
+        .visible_when(Box::new(|state: &LibraryState| state.page > 0)),
+    );
+
+    scene.add(LabelUi::new(
+        point2(screen_width as i32 / 2 - 150, button_top + 15),
+        vec2(300u32, 50u32),
+        Box::new(|state: &LibraryState| {
+            format!("Page {} of {}", state.page + 1, state.page_count())
+        }),
+    ));
+
+    scene.add(
+        ButtonUi::new(
+            point2(screen_width as i32 - 100 - button_size.x as i32, button_top),
+            button_size,
+            "Next",
+            Box::new(
+                |_ui: Rc<RefCell<&mut UiController>>, state: &mut LibraryState| {
+                    state.page += 1;
+                    state.confirm_delete = None;
+                },
+            ),
+        )
+        .visible_when(Box::new(|state: &LibraryState| {
+            state.page + 1 < state.page_count()
+        })),
+    );
+
+    scene.add(ButtonUi::new(
+        point2(100, button_top + 120),
+        button_size,
+        "Menu",
+        Box::new(
+            |ui: Rc<RefCell<&mut UiController>>, _state: &mut LibraryState| {
+                let state = MenuState {
+                    settings: settings::load(),
+                    error: None,
+                };
+                let scene = create_menu_scene(&ui.borrow_mut().context, state);
+                UiController::change_scene(ui.clone(), Rc::from(RefCell::new(scene)), true);
+            },
+        ),
+    ));
+
+    scene.add(LabelUi::new(
+        point2(400, button_top + 135),
+        vec2(904u32, 50u32),
+        Box::new(|state: &LibraryState| state.error.clone().unwrap_or_default()),
+    ));
+
+    return scene;
+}
+
+fn create_review_scene(
+    ctx: &ApplicationContext,
+    game_controller: Box<dyn GameController>,
+    library_page: usize,
+) -> ui::Scene<Box<dyn GameController>> {
+    let root = game_controller.game_tree().root();
+    let black = root.property("PB").unwrap_or("Black").to_string();
+    let white = root.property("PW").unwrap_or("White").to_string();
+
+    let board = game_controller.current_game_state();
+    let (width, height) = (board.width, board.height);
+    let mut review = ui::Scene::new(game_controller);
+    review.add(board_ui::BoardUi::new(ctx, width, height));
+    review.add(player_ui::PlayerUi::new(ctx, &white, true, Player::White));
+    review.add(player_ui::PlayerUi::new(ctx, &black, false, Player::Black));
+    review.add(quit_ui::QuitUi::new(ctx));
+
+    let (screen_height, screen_width) = ctx.get_dimensions();
+    let button_size = vec2(240u32, 80u32);
+    let button_top = screen_height as i32 - 224;
+    let button_right = screen_width as i32 - 50 - button_size.x as i32;
+
+    // Each step redraws just the board and the player bars, which refresh their own areas
+    let steps = [
+        ("First", first as fn(&mut Box<dyn GameController>)),
+        ("Previous", undo),
+        ("Next", redo),
+        ("Last", last),
+    ];
+    for (i, (label, step)) in steps.into_iter().enumerate() {
+        review.add(ButtonUi::new(
+            point2(50 + i as i32 * (button_size.x as i32 + 40), 144),
+            button_size,
+            label,
+            Box::new(
+                move |_ui: Rc<RefCell<&mut UiController>>, state: &mut Box<dyn GameController>| {
+                    step(state)
+                },
+            ),
+        ));
     }
+
+    review.add(ButtonUi::new(
+        point2(button_right, button_top),
+        button_size,
+        "Library",
+        Box::new(
+            move |ui: Rc<RefCell<&mut UiController>>, _state: &mut Box<dyn GameController>| {
+                let scene = create_library_scene(&ui.borrow_mut().context, library_page);
+                UiController::change_scene(ui.clone(), Rc::from(RefCell::new(scene)), true);
+            },
+        ),
+    ));
+
+    review.add(LabelUi::new(
+        point2(50, button_top + 30),
+        vec2(500u32, 50u32),
+        Box::new(|state: &Box<dyn GameController>| match state.move_error() {
+            Some(error) => error.to_string(),
+            None => format!("Move {}", state.game_tree().move_number()),
+        }),
+    ));
+
+    return review;
 }
 
-fn controller_options_from_name(name: &str) -> Vec<ControllerOption> {
-    match name {
-        "1-Player" => one_player_controller::options(),
-        "2-Player" => two_player_controller::options(),
-        "OGS" => ogs_controller::options(),
-        _ => panic!("Unknown game type"),
+// The computer's moves arrive in the background, and wake up the event loop so they're shown
+fn notify(waker: ui::Waker) -> Notify {
+    Box::new(move || waker.wake())
+}
+
+fn new_game(settings: &GameSettings, waker: ui::Waker) -> Result<Box<dyn GameController>, String> {
+    settings.validate()?;
+    let directory = autosave::data_directory();
+    match settings.mode {
+        Mode::OnePlayer => Ok(Box::new(one_player_controller::OnePlayerController::new(
+            settings,
+            directory,
+            notify(waker),
+        )?)),
+        Mode::TwoPlayer => Ok(Box::new(two_player_controller::TwoPlayerController::new(
+            settings, directory,
+        )?)),
+        Mode::Ogs => Ok(Box::new(ogs_controller::OgsController::new(
+            settings, directory,
+        )?)),
+    }
+}
+
+fn resume_game(waker: ui::Waker) -> Result<Box<dyn GameController>, String> {
+    let directory = autosave::data_directory();
+    let (tree, settings) = autosave::load(&directory)?;
+    match settings.mode {
+        Mode::OnePlayer => Ok(Box::new(
+            one_player_controller::OnePlayerController::resume(tree, directory, notify(waker)),
+        )),
+        Mode::TwoPlayer => Ok(Box::new(
+            two_player_controller::TwoPlayerController::resume(tree, directory),
+        )),
+        Mode::Ogs => Ok(Box::new(ogs_controller::OgsController::resume(
+            tree, directory,
+        ))),
+    }
+}
+
+fn controller_options(mode: Mode) -> Vec<ControllerOption> {
+    match mode {
+        Mode::OnePlayer => one_player_controller::options(),
+        Mode::TwoPlayer => two_player_controller::options(),
+        Mode::Ogs => ogs_controller::options(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::game_buttons;
+    use cgmath::vec2;
+
+    #[test]
+    fn game_buttons_kept_apart() {
+        // The reMarkable's screen
+        let size = vec2(240u32, 80u32);
+        let buttons = game_buttons(1404, 1872, size);
+        for (i, a) in buttons.iter().enumerate() {
+            for b in &buttons[i + 1..] {
+                let apart = a.position.x + size.x as i32 <= b.position.x
+                    || b.position.x + size.x as i32 <= a.position.x
+                    || a.position.y + size.y as i32 <= b.position.y
+                    || b.position.y + size.y as i32 <= a.position.y;
+                let shown_together = a.phases.iter().any(|phase| b.phases.contains(phase));
+                assert!(
+                    apart || !shown_together,
+                    "{} and {} share a spot",
+                    a.label,
+                    b.label
+                );
+            }
+        }
     }
 }
@@ -1,35 +1,215 @@
+use crate::clock::GameClock;
 use crate::game_controller::{ControllerOption, GameController};
-use crate::go::BoardState;
+use crate::game_tree::GameTree;
+use crate::go::{BoardState, GamePhase, MoveError, Player};
+use crate::live_game::LiveGame;
+use crate::settings::{BoardSize, Clock, GameSettings};
 use cgmath::Point2;
-use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+// The server's number for the game, kept on the root of the record so that a resumed game knows
+// which one to reconnect to
+const GAME_ID_PROPERTY: &str = "XOGSGAME";
 
 pub struct OgsController {
-    state: BoardState,
+    game: LiveGame,
+    // Online games only take a move back once the opponent agrees, so an undo waits here until
+    // the server passes on their answer
+    undo_requested: bool,
 }
 
 impl OgsController {
-    pub(crate) fn new(options: HashMap<String, String>) -> OgsController {
-        let board_size = options.get("Board Size").unwrap();
-        let initial_state = BoardState::new(board_size.split("x").next().unwrap().parse().unwrap());
+    pub(crate) fn new(
+        settings: &GameSettings,
+        directory: PathBuf,
+    ) -> Result<OgsController, String> {
+        settings.validate()?;
+        let BoardSize { width, height } = settings.board_size;
+        Ok(OgsController {
+            game: LiveGame::start(settings, BoardState::new(width, height), directory),
+            undo_requested: false,
+        })
+    }
+
+    pub(crate) fn resume(tree: GameTree, directory: PathBuf) -> OgsController {
         OgsController {
-            state: initial_state,
+            game: LiveGame::resume(tree, directory),
+            undo_requested: false,
         }
     }
+
+    // Nothing talks to the server yet, so these are kept for the network layer
+    #[allow(dead_code)]
+    pub(crate) fn game_id(&self) -> Option<u64> {
+        self.game
+            .tree
+            .root()
+            .property(GAME_ID_PROPERTY)?
+            .parse()
+            .ok()
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn set_game_id(&mut self, id: u64) {
+        self.game
+            .tree
+            .root_mut()
+            .set_property(GAME_ID_PROPERTY, &id.to_string());
+        self.game.save();
+    }
+
+    // Takes the move back if the opponent accepted the undo request, for the network layer to call
+    // with their answer
+    #[allow(dead_code)]
+    pub(crate) fn undo_answered(&mut self, accepted: bool) {
+        if self.undo_requested && accepted {
+            let _ = self.game.undo(|_| true);
+        }
+        self.undo_requested = false;
+    }
 }
 
 pub fn options() -> Vec<ControllerOption> {
     vec![
         ControllerOption::new("Board Size", vec!["9x9", "13x13", "19x19"]),
-        ControllerOption::new("Clock", vec!["Blitz", "Rapid", "None"]),
+        ControllerOption::new(
+            "Clock",
+            Clock::ALL.iter().map(|clock| clock.name()).collect(),
+        ),
     ]
 }
 
 impl GameController for OgsController {
+    fn game_tree(&self) -> &GameTree {
+        &self.game.tree
+    }
+
     fn current_game_state(&self) -> &BoardState {
-        &self.state
+        self.game.tree.current_state()
+    }
+
+    fn try_play(&mut self, point: Point2<usize>) -> Result<(), MoveError> {
+        self.game.play(point)
+    }
+
+    fn move_error(&self) -> Option<&MoveError> {
+        self.game.move_error.as_ref()
+    }
+
+    fn save_error(&self) -> Option<&io::Error> {
+        self.game.save_error.as_ref()
+    }
+
+    fn clock(&self) -> Option<&GameClock> {
+        self.game.clock.as_ref()
+    }
+
+    fn check_time(&mut self) {
+        self.game.check_time();
+    }
+
+    fn pass(&mut self) -> Result<(), MoveError> {
+        self.game.pass()
+    }
+
+    fn resign(&mut self) -> Result<(), &str> {
+        let player = self.game.tree.current_state().current_player;
+        self.game.resign(player)
     }
 
-    fn try_play(&mut self, point: Point2<usize>) -> Result<(), &str> {
-        self.state.try_play(point)
+    fn toggle_handicap_stone(&mut self, point: Point2<usize>) -> Result<(), &str> {
+        self.game.toggle_handicap_stone(point)
+    }
+
+    fn confirm_handicap(&mut self) -> Result<(), &str> {
+        self.game.confirm_handicap()
+    }
+
+    fn toggle_dead(&mut self, point: Point2<usize>) -> Result<(), &str> {
+        self.game.toggle_dead(point)
+    }
+
+    fn accept_score(&mut self, player: Player) -> Result<(), &str> {
+        self.game.accept_score(player)
+    }
+
+    // Asks the opponent to take the last move back. Nothing changes until they answer
+    fn undo(&mut self) -> Result<(), &str> {
+        if self.game.tree.current_state().phase == GamePhase::Finished {
+            return Err("The game is over");
+        }
+        if self.game.tree.move_number() == 0 {
+            return Err("There are no moves to undo");
+        }
+        if self.undo_requested {
+            return Err("Your opponent hasn't answered the last undo request");
+        }
+
+        self.undo_requested = true;
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<(), &str> {
+        Err("Moves can't be redone in an online game")
+    }
+
+    fn pending_request(&self) -> Option<&str> {
+        match self.undo_requested {
+            true => Some("Waiting for your opponent to accept the undo"),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OgsController;
+    use crate::autosave;
+    use crate::game_controller::GameController;
+    use crate::settings::GameSettings;
+    use crate::sgf;
+    use cgmath::point2;
+
+    #[test]
+    fn game_id_is_saved() {
+        let mut settings = GameSettings::default();
+        settings.set("Mode", "OGS").unwrap();
+        settings.set("Board Size", "9x9").unwrap();
+        let directory = autosave::test_directory("game_id_is_saved");
+        let mut controller = OgsController::new(&settings, directory.clone()).unwrap();
+        assert_eq!(controller.game_id(), None);
+        controller.set_game_id(12345);
+        assert!(controller.save_error().is_none());
+
+        let tree = sgf::read(&sgf::write(controller.game_tree())).unwrap();
+        assert_eq!(
+            OgsController::resume(tree, directory).game_id(),
+            Some(12345)
+        );
+    }
+
+    #[test]
+    fn undo_waits_for_opponent() {
+        let mut settings = GameSettings::default();
+        settings.set("Mode", "OGS").unwrap();
+        let directory = autosave::test_directory("undo_waits_for_opponent");
+        let mut controller = OgsController::new(&settings, directory).unwrap();
+        assert!(controller.undo().is_err());
+
+        controller.try_play(point2(3, 3)).unwrap();
+        assert_eq!(controller.undo(), Ok(()));
+        assert!(controller.pending_request().is_some());
+        assert!(controller.undo().is_err());
+        assert_eq!(controller.game_tree().move_number(), 1);
+
+        controller.undo_answered(false);
+        assert_eq!(controller.game_tree().move_number(), 1);
+        assert_eq!(controller.undo(), Ok(()));
+        controller.undo_answered(true);
+        assert_eq!(controller.game_tree().move_number(), 0);
+        assert_eq!(controller.pending_request(), None);
+        assert!(controller.redo().is_err());
+        assert!(controller.save_error().is_none());
     }
 }
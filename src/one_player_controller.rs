@@ -1,36 +1,331 @@
+use crate::autosave;
+use crate::bot::Bot;
+use crate::bot_worker::{BotWorker, Notify};
 use crate::game_controller::{ControllerOption, GameController};
-use crate::go::BoardState;
+use crate::game_tree::GameTree;
+use crate::go::{other_player, BoardState, GamePhase, MoveError, Player};
+use crate::live_game::LiveGame;
+use crate::rules::RuleSet;
+use crate::settings::{Difficulty, GameSettings};
 use cgmath::Point2;
-use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
 
 pub struct OnePlayerController {
-    state: BoardState,
+    game: LiveGame,
+    // The side the person plays, with the computer playing the other
+    human: Player,
+    bot: BotWorker,
+}
+
+// A different seed for each game, so the computer doesn't play the same game every time
+fn seed() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64)
 }
 
 impl OnePlayerController {
-    pub(crate) fn new(options: HashMap<String, String>) -> OnePlayerController {
-        let board_size = options.get("Board Size").unwrap();
-        let initial_state = BoardState::new(board_size.split("x").next().unwrap().parse().unwrap());
-        OnePlayerController {
-            state: initial_state,
+    // The computer's moves are worked out in the background, and notify is called as each one is
+    // ready to be picked up with poll
+    pub(crate) fn new(
+        settings: &GameSettings,
+        directory: PathBuf,
+        notify: Notify,
+    ) -> Result<OnePlayerController, String> {
+        let mut controller = OnePlayerController {
+            game: LiveGame::start(settings, settings.initial_state()?, directory),
+            human: Player::Black,
+            bot: BotWorker::new(Bot::new(settings.difficulty, seed()), notify),
+        };
+        controller.reply();
+        Ok(controller)
+    }
+
+    pub(crate) fn resume(
+        tree: GameTree,
+        directory: PathBuf,
+        notify: Notify,
+    ) -> OnePlayerController {
+        let difficulty = autosave::settings(&tree).difficulty;
+        let mut controller = OnePlayerController {
+            game: LiveGame::resume(tree, directory),
+            human: Player::Black,
+            bot: BotWorker::new(Bot::new(difficulty, seed()), notify),
+        };
+        controller.reply();
+        controller
+    }
+
+    // The computer moves whenever it's its turn, which is first in a handicap game
+    fn reply(&mut self) {
+        let state = self.game.tree.current_state();
+        if state.phase == GamePhase::Playing
+            && state.current_player != self.human
+            && !self.bot.is_thinking()
+        {
+            self.bot.think(state.clone());
         }
     }
+
+    fn humans_turn(&self) -> bool {
+        let state = self.game.tree.current_state();
+        state.phase != GamePhase::Playing || state.current_player == self.human
+    }
 }
 
 pub fn options() -> Vec<ControllerOption> {
     vec![
-        ControllerOption::new("Board Size", vec!["9x9", "13x13", "19x19"]),
-        ControllerOption::new("Difficulty", vec!["Easy", "Medium", "Hard"]),
-        ControllerOption::new("Handicap", vec!["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"]),
+        ControllerOption::new(
+            "Board Size",
+            vec!["5x5", "7x7", "9x9", "13x13", "19x19", "21x21"],
+        ),
+        ControllerOption::new(
+            "Rules",
+            RuleSet::ALL.iter().map(|rules| rules.name()).collect(),
+        ),
+        ControllerOption::new(
+            "Komi",
+            vec!["Auto", "-6.5", "0", "0.5", "5.5", "6.5", "7", "7.5"],
+        ),
+        ControllerOption::new(
+            "Difficulty",
+            Difficulty::ALL
+                .iter()
+                .map(|difficulty| difficulty.name())
+                .collect(),
+        ),
+        ControllerOption::new(
+            "Handicap",
+            vec!["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"],
+        ),
     ]
 }
 
 impl GameController for OnePlayerController {
+    fn game_tree(&self) -> &GameTree {
+        &self.game.tree
+    }
+
     fn current_game_state(&self) -> &BoardState {
-        &self.state
+        self.game.tree.current_state()
+    }
+
+    fn try_play(&mut self, point: Point2<usize>) -> Result<(), MoveError> {
+        if !self.humans_turn() {
+            self.game.move_error = Some(MoveError::NotYourTurn);
+            return Err(MoveError::NotYourTurn);
+        }
+
+        self.game.play(point)?;
+        self.reply();
+        Ok(())
+    }
+
+    fn move_error(&self) -> Option<&MoveError> {
+        self.game.move_error.as_ref()
+    }
+
+    fn save_error(&self) -> Option<&io::Error> {
+        self.game.save_error.as_ref()
+    }
+
+    fn pass(&mut self) -> Result<(), MoveError> {
+        if !self.humans_turn() {
+            self.game.move_error = Some(MoveError::NotYourTurn);
+            return Err(MoveError::NotYourTurn);
+        }
+
+        self.game.pass()?;
+        self.reply();
+        Ok(())
+    }
+
+    fn resign(&mut self) -> Result<(), &str> {
+        self.game.resign(self.human)?;
+        self.bot.cancel();
+        Ok(())
+    }
+
+    fn toggle_handicap_stone(&mut self, point: Point2<usize>) -> Result<(), &str> {
+        self.game.toggle_handicap_stone(point)
+    }
+
+    fn confirm_handicap(&mut self) -> Result<(), &str> {
+        self.game.confirm_handicap()?;
+        self.reply();
+        Ok(())
+    }
+
+    fn toggle_dead(&mut self, point: Point2<usize>) -> Result<(), &str> {
+        self.game.toggle_dead(point)
+    }
+
+    fn accept_score(&mut self, player: Player) -> Result<(), &str> {
+        self.game.accept_score(player)
     }
 
-    fn try_play(&mut self, point: Point2<usize>) -> Result<(), &str> {
-        self.state.try_play(point)
+    // The computer's reply is taken back along with the move it answered, so it's the person's
+    // turn again
+    fn undo(&mut self) -> Result<(), &str> {
+        let human = self.human;
+        self.game.undo(|state| state.current_player == human)?;
+
+        // Whatever the computer was thinking about has just been taken back
+        self.bot.cancel();
+        self.reply();
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<(), &str> {
+        let human = self.human;
+        self.game.redo(|state| state.current_player == human)?;
+
+        // Redoing up to a move the computer hasn't answered yet means it needs to answer it
+        self.bot.cancel();
+        self.reply();
+        Ok(())
+    }
+
+    fn thinking(&self) -> Option<Player> {
+        self.bot.is_thinking().then(|| other_player(self.human))
+    }
+
+    fn poll(&mut self) -> bool {
+        let Some(played) = self.bot.take_move() else {
+            return false;
+        };
+
+        // The worker only answers the position it was last given, which is always the one being
+        // played, so its move should always fit the game
+        let result = match played {
+            Some(point) => self.game.play(point),
+            None => self.game.pass(),
+        };
+        debug_assert!(result.is_ok(), "The computer's move was refused");
+        self.game.move_error = result.err();
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OnePlayerController;
+    use crate::autosave;
+    use crate::bot::Bot;
+    use crate::bot_worker::BotWorker;
+    use crate::game_controller::GameController;
+    use crate::go::{MoveError, Player};
+    use crate::settings::{GameSettings, Mode};
+    use cgmath::point2;
+    use std::thread;
+    use std::time::Duration;
+
+    fn small_game(name: &str) -> OnePlayerController {
+        let mut settings = GameSettings {
+            mode: Mode::OnePlayer,
+            ..GameSettings::default()
+        };
+        settings.set("Board Size", "9x9").unwrap();
+        let directory = autosave::test_directory(name);
+        let mut controller =
+            OnePlayerController::new(&settings, directory, Box::new(|| {})).unwrap();
+        controller.bot = BotWorker::new(Bot::with_playouts(50, 1), Box::new(|| {}));
+        controller
+    }
+
+    fn wait_for_reply(controller: &mut OnePlayerController) {
+        while !controller.poll() {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn computer_replies() {
+        let mut controller = small_game("computer_replies");
+        controller.try_play(point2(4, 4)).unwrap();
+        assert_eq!(controller.thinking(), Some(Player::White));
+
+        // The person waits for the computer's move before playing again
+        assert_eq!(
+            controller.try_play(point2(2, 2)),
+            Err(MoveError::NotYourTurn)
+        );
+        assert_eq!(controller.pass(), Err(MoveError::NotYourTurn));
+        assert_eq!(controller.move_error(), Some(&MoveError::NotYourTurn));
+
+        wait_for_reply(&mut controller);
+        assert_eq!(controller.game_tree().move_number(), 2);
+        assert_eq!(controller.thinking(), None);
+        assert_eq!(
+            controller.current_game_state().current_player,
+            Player::Black
+        );
+
+        // A refused move isn't answered
+        assert!(controller.try_play(point2(4, 4)).is_err());
+        assert_eq!(controller.thinking(), None);
+        assert!(controller.save_error().is_none());
+    }
+
+    #[test]
+    fn undo_takes_back_reply() {
+        let mut controller = small_game("undo_takes_back_reply");
+        controller.try_play(point2(2, 2)).unwrap();
+        wait_for_reply(&mut controller);
+        controller.try_play(point2(6, 6)).unwrap();
+        wait_for_reply(&mut controller);
+
+        assert_eq!(controller.undo(), Ok(()));
+        assert_eq!(controller.game_tree().move_number(), 2);
+        assert_eq!(controller.undo(), Ok(()));
+        assert_eq!(controller.game_tree().move_number(), 0);
+        assert_eq!(
+            controller.current_game_state().current_player,
+            Player::Black
+        );
+        assert!(controller.undo().is_err());
+
+        assert_eq!(controller.redo(), Ok(()));
+        assert_eq!(controller.game_tree().move_number(), 2);
+        assert_eq!(controller.thinking(), None);
+        assert!(controller.save_error().is_none());
+    }
+
+    #[test]
+    fn undo_while_thinking() {
+        let mut controller = small_game("undo_while_thinking");
+        controller.try_play(point2(2, 2)).unwrap();
+        assert_eq!(controller.undo(), Ok(()));
+        assert_eq!(controller.thinking(), None);
+
+        // The move the computer was working on never arrives
+        thread::sleep(Duration::from_millis(100));
+        assert!(!controller.poll());
+        assert_eq!(controller.game_tree().move_number(), 0);
+        assert!(controller.save_error().is_none());
+    }
+
+    #[test]
+    fn computer_starts_handicap_games() {
+        let mut settings = GameSettings {
+            mode: Mode::OnePlayer,
+            ..GameSettings::default()
+        };
+        settings.set("Board Size", "9x9").unwrap();
+        settings.set("Handicap", "2").unwrap();
+        let directory = autosave::test_directory("computer_starts_handicap_games");
+        let mut controller =
+            OnePlayerController::new(&settings, directory, Box::new(|| {})).unwrap();
+        assert_eq!(controller.thinking(), Some(Player::White));
+
+        wait_for_reply(&mut controller);
+        assert_eq!(controller.game_tree().move_number(), 1);
+        assert_eq!(
+            controller.current_game_state().current_player,
+            Player::Black
+        );
+        assert!(controller.save_error().is_none());
     }
 }
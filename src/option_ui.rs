@@ -1,5 +1,5 @@
 use crate::text::TextAlignment;
-use crate::ui::{UiComponent, UiController};
+use crate::ui::{UiComponent, UiController, UiEvent};
 use crate::{drawing, text, ui};
 use cgmath::{point2, vec2, Point2, Vector2};
 use libremarkable::appctx::ApplicationContext;
@@ -9,13 +9,14 @@ use libremarkable::framebuffer::common::{
 use libremarkable::framebuffer::{FramebufferDraw, FramebufferRefresh, PartialRefreshMode};
 use libremarkable::input::{InputEvent, MultitouchEvent};
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
 use std::string::String;
 
-pub struct OptionUi {
+// A row of choices for one setting, with the one in the scene's state highlighted
+pub struct OptionUi<State> {
     option_names: Vec<String>,
-    selected: usize,
+    // None when the state's value isn't one of the choices
+    selected: Option<usize>,
     box_starts: Vec<Point2<i32>>,
     box_size: Vector2<u32>,
     size: Vector2<u32>,
@@ -23,17 +24,21 @@ pub struct OptionUi {
     text_size: i32,
     title: String,
     title_position: Point2<i32>,
-    callback: Box<dyn Fn(Rc<RefCell<&mut UiController>>, &mut HashMap<String, String>, &String)>,
+    // The choice the state currently holds
+    value: Box<dyn Fn(&State) -> String>,
+    // Called with the choice which was pressed, to apply it to the state
+    callback: Box<dyn Fn(Rc<RefCell<&mut UiController>>, &mut State, &String)>,
 }
 
-impl OptionUi {
+impl<State> OptionUi<State> {
     pub fn new(
         ctx: &ApplicationContext,
         vertical_position: i32,
         title: String,
         option_names: Vec<String>,
-        callback: Box<dyn Fn(Rc<RefCell<&mut UiController>>, &mut HashMap<String, String>, &String)>,
-    ) -> OptionUi {
+        value: Box<dyn Fn(&State) -> String>,
+        callback: Box<dyn Fn(Rc<RefCell<&mut UiController>>, &mut State, &String)>,
+    ) -> OptionUi<State> {
         let minimum_border = 250;
         let title_offset = vec2(30, -50);
         let height = 80;
@@ -68,7 +73,7 @@ impl OptionUi {
 
         OptionUi {
             option_names,
-            selected: 0,
+            selected: None,
             box_starts,
             box_size,
             size,
@@ -76,23 +81,25 @@ impl OptionUi {
             text_size,
             title,
             title_position,
+            value,
             callback,
         }
     }
 }
 
-impl UiComponent<HashMap<String, String>> for OptionUi {
-    fn initialize(&mut self, _state: &mut HashMap<String, String>) {
-        self.selected = self.option_names.iter().position(|name| name == &_state[&self.title]).unwrap();
+impl<State> UiComponent<State> for OptionUi<State> {
+    fn initialize(&mut self, state: &mut State) {
+        let value = (self.value)(state);
+        self.selected = self.option_names.iter().position(|name| *name == value);
     }
 
     fn handle_event(
         &mut self,
         ui: Rc<RefCell<&mut UiController>>,
-        state: &mut HashMap<String, String>,
-        event: &InputEvent,
+        state: &mut State,
+        event: &UiEvent,
     ) {
-        if let InputEvent::MultitouchEvent { event, .. } = event {
+        if let UiEvent::Input(InputEvent::MultitouchEvent { event, .. }) = event {
             if let MultitouchEvent::Press { finger } = event {
                 for i in 0..self.box_starts.len() {
                     let box_start = self.box_starts[i];
@@ -102,12 +109,8 @@ impl UiComponent<HashMap<String, String>> for OptionUi {
                         && finger.pos.y >= box_start.y as u16
                         && finger.pos.y < box_end.y as u16
                     {
-                        self.selected = i;
-
-                        let selected_option = &self.option_names[self.selected];
-                        state.insert(self.title.clone(), selected_option.clone());
-
-                        (self.callback)(ui.clone(), state, selected_option);
+                        (self.callback)(ui.clone(), state, &self.option_names[i]);
+                        self.initialize(state);
 
                         ui::post_redraw();
                     }
@@ -116,7 +119,7 @@ impl UiComponent<HashMap<String, String>> for OptionUi {
         }
     }
 
-    fn draw(&self, ui: Rc<RefCell<&mut UiController>>, _state: &HashMap<String, String>) {
+    fn draw(&self, ui: Rc<RefCell<&mut UiController>>, _state: &State) {
         let fb = ui.borrow_mut().context.get_framebuffer_ref();
 
         text::draw_text(
@@ -129,7 +132,7 @@ impl UiComponent<HashMap<String, String>> for OptionUi {
         );
 
         for i in 0..self.option_names.len() {
-            if i == self.selected {
+            if self.selected == Some(i) {
                 fb.fill_rect(self.box_starts[i], self.box_size, color::BLACK);
                 text::draw_text(
                     fb,
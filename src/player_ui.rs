@@ -1,19 +1,21 @@
 use crate::game_controller::GameController;
-use crate::go::Player;
-use crate::ui::{UiComponent, UiController};
-use crate::{drawing, text};
+use crate::go::{GamePhase, Player};
+use crate::ui::{UiComponent, UiController, UiEvent};
+use crate::{drawing, text, ui};
 use cgmath::{point2, vec2, Point2, Vector2};
 use libremarkable::appctx::ApplicationContext;
 use libremarkable::framebuffer::common::{
     color, display_temp, dither_mode, mxcfb_rect, waveform_mode, DRAWING_QUANT_BIT,
 };
-use libremarkable::framebuffer::{
-    FramebufferDraw, FramebufferRefresh, PartialRefreshMode,
-};
+use libremarkable::framebuffer::core::Framebuffer;
+use libremarkable::framebuffer::{FramebufferDraw, FramebufferRefresh, PartialRefreshMode};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::string::String;
 
+// Goes off every second while a timed game is on screen
+pub const CLOCK_TIMER: &str = "clock";
+
 pub struct PlayerUi {
     player: Player,
     player_name: String,
@@ -22,6 +24,8 @@ pub struct PlayerUi {
     text_size: i32,
     rect_start: Point2<i32>,
     rect_size: Vector2<u32>,
+    clock_start: Point2<i32>,
+    clock_size: Vector2<u32>,
 }
 
 impl PlayerUi {
@@ -43,6 +47,13 @@ impl PlayerUi {
         let captures_position =
             rect_start + vec2(screen_width as i32 - padding - height as i32, text_topline);
 
+        let clock_size = vec2(320u32, 60u32);
+        let clock_start = rect_start
+            + vec2(
+                (screen_width - clock_size.x) as i32 / 2,
+                (height - clock_size.y) as i32 / 2,
+            );
+
         PlayerUi {
             player,
             player_name: String::from(player_name),
@@ -51,29 +62,113 @@ impl PlayerUi {
             text_size,
             rect_start,
             rect_size,
+            clock_start,
+            clock_size,
         }
     }
+
+    // The clock sits in the middle of the bar in a box of its own, so that it can be redrawn alone
+    // as it counts down. It turns black for the last ten seconds
+    fn draw_clock(&self, fb: &mut Framebuffer, state: &dyn GameController) {
+        let Some(clock) = state.clock() else {
+            return;
+        };
+
+        let running_low = clock.running_low(self.player);
+        if running_low {
+            fb.fill_rect(self.clock_start, self.clock_size, color::BLACK);
+        } else {
+            fb.fill_rect(self.clock_start, self.clock_size, color::WHITE);
+            drawing::draw_rect(fb, self.clock_start, self.clock_size, 2);
+        }
+
+        text::draw_text(
+            fb,
+            self.clock_start
+                + vec2(
+                    self.clock_size.x as i32 / 2,
+                    (self.clock_size.y as i32 - self.text_size) / 2,
+                ),
+            text::TextAlignment::Centered,
+            self.text_size,
+            if running_low {
+                color::WHITE
+            } else {
+                color::BLACK
+            },
+            &clock.display(self.player),
+        );
+    }
 }
 
 impl UiComponent<Box<dyn GameController>> for PlayerUi {
+    fn handle_event(
+        &mut self,
+        ui: Rc<RefCell<&mut UiController>>,
+        state: &mut Box<dyn GameController>,
+        event: &UiEvent,
+    ) {
+        // Only the player whose clock is running can run out of time, or needs their time redrawn
+        let running = state.clock().and_then(|clock| clock.running());
+        if *event != UiEvent::Timer(CLOCK_TIMER) || running != Some(self.player) {
+            return;
+        }
+
+        state.check_time();
+        if state.game_phase() == GamePhase::Finished {
+            ui::post_redraw();
+            return;
+        }
+
+        let fb = ui.borrow_mut().context.get_framebuffer_ref();
+        self.draw_clock(fb, state.as_ref());
+
+        let refresh_rect = mxcfb_rect {
+            top: self.clock_start.y as u32,
+            left: self.clock_start.x as u32,
+            width: self.clock_size.x,
+            height: self.clock_size.y,
+        };
+
+        fb.partial_refresh(
+            &refresh_rect,
+            PartialRefreshMode::Async,
+            waveform_mode::WAVEFORM_MODE_DU,
+            display_temp::TEMP_USE_PAPYRUS,
+            dither_mode::EPDC_FLAG_USE_DITHERING_PASSTHROUGH,
+            DRAWING_QUANT_BIT,
+            false,
+        );
+    }
+
     fn draw(self: &PlayerUi, ui: Rc<RefCell<&mut UiController>>, state: &Box<dyn GameController>) {
         let fb = ui.borrow_mut().context.get_framebuffer_ref();
         let game_state = state.current_game_state();
 
-        if game_state.current_player == self.player {
+        let in_progress = matches!(game_state.phase, GamePhase::Setup | GamePhase::Playing);
+        if in_progress && game_state.current_player == self.player {
             // Use a dithered rectangle so that the update can be drawn using the direct update waveform
             drawing::dithered_fill_rect(fb, self.rect_start, self.rect_size, 8, 3);
         } else {
             fb.fill_rect(self.rect_start, self.rect_size, color::WHITE);
         }
 
+        let mut name_string = self.player_name.clone();
+        if game_state.phase == GamePhase::Scoring && game_state.accepted_by.contains(&self.player) {
+            name_string = format!("{}  Accepted", name_string);
+        } else if state.thinking() == Some(self.player) {
+            name_string = format!("{}  Thinking...", name_string);
+        } else if game_state.has_passed(self.player) {
+            name_string = format!("{}  Passed", name_string);
+        }
+
         text::draw_text(
             fb,
             self.name_position,
             text::TextAlignment::Left,
             self.text_size,
             color::BLACK,
-            self.player_name.as_str(),
+            name_string.as_str(),
         );
 
         let captures = game_state.captures_made_by(self.player);
@@ -83,7 +178,7 @@ impl UiComponent<Box<dyn GameController>> for PlayerUi {
         };
 
         if self.player == Player::White {
-            captures_string = format!("{}.5 Komi  {}", game_state.komi_minus_half, captures_string);
+            captures_string = format!("{} Komi  {}", game_state.komi, captures_string);
         }
 
         text::draw_text(
@@ -95,6 +190,8 @@ impl UiComponent<Box<dyn GameController>> for PlayerUi {
             captures_string.as_str(),
         );
 
+        self.draw_clock(fb, state.as_ref());
+
         let refresh_rect = mxcfb_rect {
             top: self.rect_start.y as u32,
             left: self.rect_start.x as u32,
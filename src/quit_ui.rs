@@ -1,5 +1,5 @@
 use crate::drawing;
-use crate::ui::{UiComponent, UiController};
+use crate::ui::{UiComponent, UiController, UiEvent};
 use cgmath::{point2, vec2, Point2, Vector2};
 use libremarkable::appctx::ApplicationContext;
 use libremarkable::framebuffer::common::{
@@ -38,9 +38,9 @@ impl<State> UiComponent<State> for QuitUi {
         self: &mut QuitUi,
         _ui: Rc<RefCell<&mut UiController>>,
         _: &mut State,
-        event: &InputEvent,
+        event: &UiEvent,
     ) {
-        if let InputEvent::MultitouchEvent { event, .. } = event {
+        if let UiEvent::Input(InputEvent::MultitouchEvent { event, .. }) = event {
             if let MultitouchEvent::Release { finger } = event {
                 if finger.pos.x >= self.position.x as u16 && finger.pos.y < self.size.y as u16 {
                     // TODO only exit scene
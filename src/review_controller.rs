@@ -0,0 +1,84 @@
+use crate::game_controller::GameController;
+use crate::game_tree::GameTree;
+use crate::go::{BoardState, MoveError, Player};
+use cgmath::Point2;
+
+// Shows a saved game, starting from its final position. Moves played on the board are added as
+// variations, and nothing is saved
+pub struct ReviewController {
+    tree: GameTree,
+    move_error: Option<MoveError>,
+}
+
+impl ReviewController {
+    pub(crate) fn new(mut tree: GameTree) -> ReviewController {
+        while tree.go_to_child(0) {}
+        ReviewController {
+            tree,
+            move_error: None,
+        }
+    }
+}
+
+impl GameController for ReviewController {
+    fn game_tree(&self) -> &GameTree {
+        &self.tree
+    }
+
+    fn current_game_state(&self) -> &BoardState {
+        self.tree.current_state()
+    }
+
+    fn try_play(&mut self, point: Point2<usize>) -> Result<(), MoveError> {
+        let result = self.tree.play(point);
+        self.move_error = result.clone().err();
+        result
+    }
+
+    fn move_error(&self) -> Option<&MoveError> {
+        self.move_error.as_ref()
+    }
+
+    fn pass(&mut self) -> Result<(), MoveError> {
+        let result = self.tree.pass();
+        self.move_error = result.clone().err();
+        result
+    }
+
+    fn resign(&mut self) -> Result<(), &str> {
+        Err("A game can't be resigned while reviewing it")
+    }
+
+    fn toggle_handicap_stone(&mut self, _point: Point2<usize>) -> Result<(), &str> {
+        Err("Handicap stones can only be placed before the game")
+    }
+
+    fn confirm_handicap(&mut self) -> Result<(), &str> {
+        Err("Handicap stones can only be placed before the game")
+    }
+
+    fn toggle_dead(&mut self, point: Point2<usize>) -> Result<(), &str> {
+        self.tree.toggle_dead(point)
+    }
+
+    fn accept_score(&mut self, _player: Player) -> Result<(), &str> {
+        Err("The count can't be accepted while reviewing")
+    }
+
+    // Stepping through the game keeps any variations which have been tried
+    fn undo(&mut self) -> Result<(), &str> {
+        self.move_error = None;
+        match self.tree.undo() {
+            true => Ok(()),
+            false => Err("This is the start of the game"),
+        }
+    }
+
+    fn redo(&mut self) -> Result<(), &str> {
+        self.move_error = None;
+        match self.tree.redo() {
+            true => Ok(()),
+            false => Err("There are no more moves"),
+        }
+    }
+}
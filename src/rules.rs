@@ -0,0 +1,107 @@
+use crate::go::{KoRule, Komi, ScoringMethod};
+
+// The rule sets a game can be played under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleSet {
+    Japanese,
+    Chinese,
+    Aga,
+    NewZealand,
+    TrompTaylor,
+}
+
+// How white is compensated for black's handicap stones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandicapCompensation {
+    None,
+    // White gets a point for each handicap stone
+    PerStone,
+    // White gets a point for each handicap stone after the first
+    PerStoneAfterFirst,
+}
+
+impl HandicapCompensation {
+    pub fn points(&self, handicap: u32) -> f32 {
+        match self {
+            HandicapCompensation::None => 0.0,
+            HandicapCompensation::PerStone => handicap as f32,
+            HandicapCompensation::PerStoneAfterFirst => handicap.saturating_sub(1) as f32,
+        }
+    }
+}
+
+impl RuleSet {
+    pub const ALL: [RuleSet; 5] = [
+        RuleSet::Japanese,
+        RuleSet::Chinese,
+        RuleSet::Aga,
+        RuleSet::NewZealand,
+        RuleSet::TrompTaylor,
+    ];
+
+    // The short name shown in the menu
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuleSet::Japanese => "Japanese",
+            RuleSet::Chinese => "Chinese",
+            RuleSet::Aga => "AGA",
+            RuleSet::NewZealand => "NZ",
+            RuleSet::TrompTaylor => "TT",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RuleSet> {
+        RuleSet::ALL.into_iter().find(|rules| rules.name() == name)
+    }
+
+    // Whether a move which leaves its own group without liberties is legal. That group is removed
+    pub fn suicide_allowed(&self) -> bool {
+        matches!(self, RuleSet::NewZealand | RuleSet::TrompTaylor)
+    }
+
+    // Whether black may place handicap stones anywhere, rather than on the fixed points
+    pub fn free_handicap(&self) -> bool {
+        !matches!(self, RuleSet::Japanese)
+    }
+
+    pub fn ko_rule(&self) -> KoRule {
+        match self {
+            RuleSet::Japanese => KoRule::Simple,
+            RuleSet::Chinese | RuleSet::TrompTaylor => KoRule::PositionalSuperko,
+            RuleSet::Aga | RuleSet::NewZealand => KoRule::SituationalSuperko,
+        }
+    }
+
+    pub fn scoring(&self) -> ScoringMethod {
+        match self {
+            RuleSet::Japanese => ScoringMethod::Territory,
+            _ => ScoringMethod::Area,
+        }
+    }
+
+    pub fn default_komi(&self) -> Komi {
+        match self {
+            RuleSet::Japanese => Komi::from_half_points(13),
+            RuleSet::NewZealand => Komi::from_half_points(14),
+            _ => Komi::from_half_points(15),
+        }
+    }
+
+    // Handicap games are played without komi, apart from the half point which breaks ties
+    pub fn komi_for_handicap(&self, handicap: u32) -> Komi {
+        match handicap {
+            0 => self.default_komi(),
+            _ => Komi::from_half_points(1),
+        }
+    }
+
+    // Handicap stones are only compensated under area scoring, where each stone would otherwise
+    // be worth a point to black
+    pub fn handicap_compensation(&self) -> HandicapCompensation {
+        match self {
+            RuleSet::Chinese => HandicapCompensation::PerStone,
+            RuleSet::Aga => HandicapCompensation::PerStoneAfterFirst,
+            _ => HandicapCompensation::None,
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use crate::go::{parse_board_size, BoardState, Komi};
+use crate::rules::RuleSet;
+use crate::utility::home_directory;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// Who the game is played against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    OnePlayer,
+    TwoPlayer,
+    Ogs,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 3] = [Mode::OnePlayer, Mode::TwoPlayer, Mode::Ogs];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::OnePlayer => "1-Player",
+            Mode::TwoPlayer => "2-Player",
+            Mode::Ogs => "OGS",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Mode> {
+        Mode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    Blitz,
+    Rapid,
+    Canadian,
+    Absolute,
+    Untimed,
+}
+
+impl Clock {
+    pub const ALL: [Clock; 5] = [
+        Clock::Blitz,
+        Clock::Rapid,
+        Clock::Canadian,
+        Clock::Absolute,
+        Clock::Untimed,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Clock::Blitz => "Blitz",
+            Clock::Rapid => "Rapid",
+            Clock::Canadian => "Canadian",
+            Clock::Absolute => "Absolute",
+            Clock::Untimed => "None",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Clock> {
+        Clock::ALL.into_iter().find(|clock| clock.name() == name)
+    }
+}
+
+// How strongly the computer plays in a 1-player game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        Difficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl BoardSize {
+    pub fn parse(text: &str) -> Result<BoardSize, String> {
+        let (width, height) = parse_board_size(text)?;
+        Ok(BoardSize { width, height })
+    }
+}
+
+impl fmt::Display for BoardSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+// The board sizes OGS plays on
+const OGS_BOARD_SIZES: [usize; 3] = [9, 13, 19];
+
+// Everything chosen in the menu before a game starts. Each mode only uses the settings it shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameSettings {
+    pub mode: Mode,
+    pub board_size: BoardSize,
+    pub rules: RuleSet,
+    // None picks the komi which suits the rules and handicap
+    pub komi: Option<Komi>,
+    pub handicap: u32,
+    pub clock: Clock,
+    pub difficulty: Difficulty,
+}
+
+impl Default for GameSettings {
+    fn default() -> GameSettings {
+        GameSettings {
+            mode: Mode::TwoPlayer,
+            board_size: BoardSize {
+                width: 19,
+                height: 19,
+            },
+            rules: RuleSet::Japanese,
+            komi: None,
+            handicap: 0,
+            clock: Clock::Rapid,
+            difficulty: Difficulty::Medium,
+        }
+    }
+}
+
+impl GameSettings {
+    // The names the menu shows for each setting
+    pub const NAMES: [&'static str; 7] = [
+        "Mode",
+        "Board Size",
+        "Rules",
+        "Komi",
+        "Handicap",
+        "Clock",
+        "Difficulty",
+    ];
+
+    // A setting as the menu shows it
+    pub fn get(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "Mode" => self.mode.name().to_string(),
+            "Board Size" => self.board_size.to_string(),
+            "Rules" => self.rules.name().to_string(),
+            "Komi" => match self.komi {
+                Some(komi) => komi.to_string(),
+                None => String::from("Auto"),
+            },
+            "Handicap" => self.handicap.to_string(),
+            "Clock" => self.clock.name().to_string(),
+            "Difficulty" => self.difficulty.name().to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    // Changes a setting from how the menu shows it. A value which can't be understood leaves the
+    // setting as it was
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("{} can't be {}", name, value);
+        match name {
+            "Mode" => self.mode = Mode::from_name(value).ok_or_else(invalid)?,
+            "Board Size" => self.board_size = BoardSize::parse(value)?,
+            "Rules" => self.rules = RuleSet::from_name(value).ok_or_else(invalid)?,
+            "Komi" if value == "Auto" => self.komi = None,
+            "Komi" => self.komi = Some(Komi::parse(value).ok_or_else(invalid)?),
+            "Handicap" => self.handicap = value.parse().map_err(|_| invalid())?,
+            "Clock" => self.clock = Clock::from_name(value).ok_or_else(invalid)?,
+            "Difficulty" => self.difficulty = Difficulty::from_name(value).ok_or_else(invalid)?,
+            _ => return Err(format!("There is no setting called {}", name)),
+        }
+        return Ok(());
+    }
+
+    // Every setting as name=value
+    pub fn to_lines(&self) -> Vec<String> {
+        GameSettings::NAMES
+            .iter()
+            .map(|name| format!("{}={}", name, self.get(name).unwrap()))
+            .collect()
+    }
+
+    // Reads settings written by to_lines. Anything which isn't understood keeps its default
+    pub fn from_lines<'a>(lines: impl Iterator<Item = &'a str>) -> GameSettings {
+        let mut settings = GameSettings::default();
+        for (name, value) in lines.filter_map(|line| line.split_once('=')) {
+            let _ = settings.set(name.trim(), value.trim());
+        }
+        return settings;
+    }
+
+    // The position a game with these settings starts from, with any handicap stones placed
+    pub fn initial_state(&self) -> Result<BoardState, String> {
+        let mut state = BoardState::new(self.board_size.width, self.board_size.height);
+        state.rules = self.rules;
+        state.komi = self.rules.default_komi();
+        state.setup_handicap(self.handicap)?;
+
+        // Without a komi of its own, the game keeps the one which suits the rules and handicap
+        if let Some(komi) = self.komi {
+            state.komi = komi;
+        }
+        return Ok(state);
+    }
+
+    // Reports combinations of settings which a game can't be started with
+    pub fn validate(&self) -> Result<(), String> {
+        let BoardSize { width, height } = self.board_size;
+        match self.mode {
+            Mode::Ogs if width != height || !OGS_BOARD_SIZES.contains(&width) => {
+                Err(String::from("OGS games are played on 9x9, 13x13 or 19x19"))
+            }
+            Mode::Ogs => Ok(()),
+            Mode::OnePlayer | Mode::TwoPlayer => self.initial_state().map(|_| ()),
+        }
+    }
+}
+
+fn path() -> PathBuf {
+    return home_directory().join(".config/gomarkable/settings");
+}
+
+// The selections from the last time the menu was used, or the defaults if there aren't any
+pub fn load() -> GameSettings {
+    match fs::read_to_string(path()) {
+        Ok(text) => GameSettings::from_lines(text.lines()),
+        Err(_) => GameSettings::default(),
+    }
+}
+
+pub fn save(settings: &GameSettings) -> io::Result<()> {
+    let path = path();
+    let mut text = settings.to_lines().join("\n");
+    text.push('\n');
+    fs::create_dir_all(path.parent().unwrap())?;
+    return fs::write(&path, text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoardSize, Clock, Difficulty, GameSettings, Mode};
+    use crate::go::Komi;
+    use crate::rules::RuleSet;
+
+    #[test]
+    fn round_trip() {
+        let settings = GameSettings {
+            mode: Mode::OnePlayer,
+            board_size: BoardSize {
+                width: 9,
+                height: 13,
+            },
+            rules: RuleSet::NewZealand,
+            komi: Some(Komi::from_half_points(-13)),
+            handicap: 3,
+            clock: Clock::Untimed,
+            difficulty: Difficulty::Hard,
+        };
+
+        let lines = settings.to_lines();
+        assert!(lines.contains(&String::from("Board Size=9x13")));
+        assert!(lines.contains(&String::from("Komi=-6.5")));
+        assert!(lines.contains(&String::from("Clock=None")));
+        assert_eq!(
+            GameSettings::from_lines(lines.iter().map(|x| x.as_str())),
+            settings
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        let text = "Mode=3-Player\nColour=Blue\nnonsense\nBoard Size=100x100\nHandicap = 4\n";
+        let settings = GameSettings::from_lines(text.lines());
+        assert_eq!(settings.mode, Mode::TwoPlayer);
+        assert_eq!(settings.board_size.to_string(), "19x19");
+        assert_eq!(settings.handicap, 4);
+
+        let mut settings = GameSettings::default();
+        assert!(settings.set("Komi", "6.25").is_err());
+        assert!(settings.set("Difficulty", "Impossible").is_err());
+        assert!(settings.set("Colour", "Blue").is_err());
+        assert_eq!(settings, GameSettings::default());
+    }
+
+    #[test]
+    fn bad_combinations() {
+        let mut settings = GameSettings::default();
+        assert_eq!(settings.validate(), Ok(()));
+
+        settings.set("Board Size", "5x5").unwrap();
+        settings.set("Handicap", "9").unwrap();
+        assert!(settings.validate().is_err());
+
+        // OGS doesn't use the handicap setting, but only has some board sizes
+        settings.set("Mode", "OGS").unwrap();
+        assert!(settings.validate().is_err());
+        settings.set("Board Size", "13x13").unwrap();
+        assert_eq!(settings.validate(), Ok(()));
+    }
+}
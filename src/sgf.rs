@@ -0,0 +1,666 @@
+// Reading and writing games in the Smart Game Format (FF[4]), which every Go server and editor
+// can exchange games with
+use crate::game_tree::{GameTree, Move, Node};
+use crate::go::{BoardState, Komi, Player, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::rules::RuleSet;
+use cgmath::{point2, Point2};
+
+// Properties which are turned into the game tree or the root position. Everything else is kept
+// on its node as it was, so it is written back out unchanged
+const ROOT_PROPERTIES: [&str; 7] = ["FF", "GM", "CA", "SZ", "KM", "HA", "RU"];
+const NODE_PROPERTIES: [&str; 6] = ["B", "W", "AB", "AW", "AE", "C"];
+
+// A node as it appears in the file, before its properties are interpreted
+struct RawNode {
+    properties: Vec<(String, Vec<String>)>,
+    children: Vec<RawNode>,
+}
+
+impl RawNode {
+    fn values(&self, name: &str) -> Vec<&str> {
+        self.properties
+            .iter()
+            .filter(|(key, _)| key == name)
+            .flat_map(|(_, values)| values.iter().map(|value| value.as_str()))
+            .collect()
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.values(name).first().cloned()
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<char> {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+        self.chars.get(self.position).cloned()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(next) if next == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(next) => Err(format!("Expected '{}' but found '{}'", expected, next)),
+            None => Err(format!("Expected '{}' but the file ended", expected)),
+        }
+    }
+
+    // A game tree is a sequence of nodes in brackets, followed by any variations
+    fn parse_tree(&mut self) -> Result<RawNode, String> {
+        self.expect('(')?;
+
+        let mut sequence = vec![];
+        while self.peek() == Some(';') {
+            self.position += 1;
+            sequence.push(self.parse_node()?);
+        }
+        if sequence.is_empty() {
+            return Err(String::from("Found a variation without any nodes"));
+        }
+
+        let mut variations = vec![];
+        while self.peek() == Some('(') {
+            variations.push(self.parse_tree()?);
+        }
+        self.expect(')')?;
+
+        // Each node in the sequence is the only child of the one before
+        let mut children = variations;
+        while let Some(mut node) = sequence.pop() {
+            node.children = children;
+            children = vec![node];
+        }
+        return Ok(children.pop().unwrap());
+    }
+
+    fn parse_node(&mut self) -> Result<RawNode, String> {
+        let mut properties = vec![];
+        while let Some(next) = self.peek() {
+            if !next.is_ascii_alphabetic() {
+                break;
+            }
+
+            // Older files spell out properties in mixed case, such as AddBlack for AB
+            let mut name = String::new();
+            while let Some(next) = self
+                .chars
+                .get(self.position)
+                .filter(|c| c.is_ascii_alphabetic())
+            {
+                if next.is_ascii_uppercase() {
+                    name.push(*next);
+                }
+                self.position += 1;
+            }
+
+            let mut values = vec![];
+            while self.peek() == Some('[') {
+                self.position += 1;
+                values.push(self.parse_value()?);
+            }
+            if values.is_empty() {
+                return Err(format!("Property {} has no value", name));
+            }
+            properties.push((name, values));
+        }
+
+        return Ok(RawNode {
+            properties,
+            children: vec![],
+        });
+    }
+
+    // Reads a value up to its closing bracket, removing escapes and soft line breaks
+    fn parse_value(&mut self) -> Result<String, String> {
+        let mut value = String::new();
+        loop {
+            let next = match self.chars.get(self.position) {
+                Some(next) => *next,
+                None => return Err(String::from("A property value isn't closed")),
+            };
+            self.position += 1;
+
+            match next {
+                ']' => return Ok(value),
+                '\\' => match self.chars.get(self.position) {
+                    Some('\n') | Some('\r') => {
+                        while let Some('\n') | Some('\r') = self.chars.get(self.position) {
+                            self.position += 1;
+                        }
+                    }
+                    Some(escaped) => {
+                        value.push(*escaped);
+                        self.position += 1;
+                    }
+                    None => {}
+                },
+                _ => value.push(next),
+            }
+        }
+    }
+}
+
+fn parse_size(text: &str) -> Result<(usize, usize), String> {
+    let (width, height) = text.split_once(':').unwrap_or((text, text));
+    let parse = |length: &str| length.trim().parse::<usize>().ok();
+
+    match (parse(width), parse(height)) {
+        (Some(width), Some(height))
+            if (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&width)
+                && (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&height) =>
+        {
+            Ok((width, height))
+        }
+        _ => Err(format!("Board size {} isn't supported", text)),
+    }
+}
+
+fn parse_coordinate(letter: char) -> Option<usize> {
+    match letter {
+        'a'..='z' => Some(letter as usize - 'a' as usize),
+        'A'..='Z' => Some(letter as usize - 'A' as usize + 26),
+        _ => None,
+    }
+}
+
+fn parse_point(text: &str, width: usize, height: usize) -> Result<Point2<usize>, String> {
+    let mut letters = text.chars();
+    let point = match (letters.next(), letters.next(), letters.next()) {
+        (Some(x), Some(y), None) => parse_coordinate(x).zip(parse_coordinate(y)),
+        _ => None,
+    };
+
+    match point {
+        Some((x, y)) if x < width && y < height => Ok(point2(x, y)),
+        _ => Err(format!("{} isn't a point on the board", text)),
+    }
+}
+
+// Reads a list of points, where a pair of corners such as aa:cc stands for the whole rectangle
+pub fn parse_points(
+    values: Vec<&str>,
+    width: usize,
+    height: usize,
+) -> Result<Vec<Point2<usize>>, String> {
+    let mut points = vec![];
+    for value in values {
+        let (first, last) = value.split_once(':').unwrap_or((value, value));
+        let first = parse_point(first, width, height)?;
+        let last = parse_point(last, width, height)?;
+        for x in first.x.min(last.x)..=first.x.max(last.x) {
+            for y in first.y.min(last.y)..=first.y.max(last.y) {
+                points.push(point2(x, y));
+            }
+        }
+    }
+
+    return Ok(points);
+}
+
+// Stones added to or cleared from the board, as a node's setup holds them
+type Setup = Vec<(Point2<usize>, Option<Player>)>;
+
+fn parse_setup(raw: &RawNode, width: usize, height: usize) -> Result<Setup, String> {
+    let mut setup = vec![];
+    for (name, stone) in [
+        ("AB", Some(Player::Black)),
+        ("AW", Some(Player::White)),
+        ("AE", None),
+    ] {
+        for point in parse_points(raw.values(name), width, height)? {
+            setup.push((point, stone));
+        }
+    }
+
+    return Ok(setup);
+}
+
+fn parse_move(raw: &RawNode, width: usize, height: usize) -> Result<Option<Move>, String> {
+    for (name, player) in [("B", Player::Black), ("W", Player::White)] {
+        let value = match raw.value(name) {
+            Some(value) => value,
+            None => continue,
+        };
+
+        // An empty move is a pass, as is tt on boards small enough that it isn't a point
+        if value.is_empty() || (value == "tt" && width <= 19 && height <= 19) {
+            return Ok(Some(Move::Pass(player)));
+        }
+        return Ok(Some(Move::Play(player, parse_point(value, width, height)?)));
+    }
+
+    return Ok(None);
+}
+
+// The properties which aren't interpreted, including any known ones whose values weren't
+// understood, so that they are written back out as they were
+fn other_properties(raw: &RawNode, known: &[&str]) -> Vec<(String, Vec<String>)> {
+    raw.properties
+        .iter()
+        .filter(|(name, _)| !known.contains(&name.as_str()))
+        .cloned()
+        .collect()
+}
+
+fn rules_from_sgf(name: &str) -> Option<RuleSet> {
+    match name.to_lowercase().as_str() {
+        "japanese" | "jp" => Some(RuleSet::Japanese),
+        "chinese" | "cn" => Some(RuleSet::Chinese),
+        "aga" => Some(RuleSet::Aga),
+        "nz" | "new zealand" => Some(RuleSet::NewZealand),
+        "tromp-taylor" | "tromp taylor" | "tt" => Some(RuleSet::TrompTaylor),
+        _ => None,
+    }
+}
+
+fn rules_to_sgf(rules: RuleSet) -> &'static str {
+    match rules {
+        RuleSet::Japanese => "Japanese",
+        RuleSet::Chinese => "Chinese",
+        RuleSet::Aga => "AGA",
+        RuleSet::NewZealand => "NZ",
+        RuleSet::TrompTaylor => "Tromp-Taylor",
+    }
+}
+
+// Sets up the position before the first move from the root node's properties
+fn root_state(raw: &RawNode) -> Result<(BoardState, Vec<&'static str>), String> {
+    let (width, height) = parse_size(raw.value("SZ").unwrap_or("19"))?;
+    let mut state = BoardState::new(width, height);
+    let mut unknown = vec![];
+
+    match raw.value("RU").map(rules_from_sgf) {
+        Some(Some(rules)) => state.rules = rules,
+        Some(None) => unknown.push("RU"),
+        None => {}
+    }
+
+    state.komi = state.rules.default_komi();
+    match raw.value("KM").map(Komi::parse) {
+        Some(Some(komi)) => state.komi = komi,
+        Some(None) => unknown.push("KM"),
+        None => {}
+    }
+
+    for (point, stone) in parse_setup(raw, width, height)? {
+        state.set_stone(point, stone);
+    }
+
+    // Handicap stones are the black stones in the root, and white moves first after them
+    state.handicap = raw.value("HA").and_then(|x| x.parse().ok()).unwrap_or(0);
+    if state.handicap >= 2 {
+        state.handicap_stones = parse_points(raw.values("AB"), width, height)?;
+        state.current_player = Player::White;
+    }
+    if raw.value("PL") == Some("W") {
+        state.current_player = Player::White;
+    }
+
+    return Ok((state, unknown));
+}
+
+fn node_from_raw(raw: &RawNode, width: usize, height: usize) -> Result<Node, String> {
+    let mut node = Node::new(parse_move(raw, width, height)?);
+    node.setup = parse_setup(raw, width, height)?;
+    node.comment = raw.value("C").unwrap_or("").to_string();
+    node.properties = other_properties(raw, &NODE_PROPERTIES);
+    return Ok(node);
+}
+
+// Adds a node read from the file, and all of its variations, after the tree's current node
+fn add_raw_node(tree: &mut GameTree, raw: &RawNode) -> Result<(), String> {
+    let state = tree.current_state();
+    let node = node_from_raw(raw, state.width, state.height)?;
+    let move_number = tree.move_number() + 1;
+    if let Err(error) = tree.add_child(node) {
+        return Err(format!("Move {} can't be played: {}", move_number, error));
+    }
+
+    for child in &raw.children {
+        add_raw_node(tree, child)?;
+    }
+    tree.go_to_parent();
+    return Ok(());
+}
+
+// Reads the first game in an SGF file, leaving the cursor on the root of its tree
+pub fn read(text: &str) -> Result<GameTree, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        position: 0,
+    };
+    // Anything before the game, such as a mail header, is skipped
+    while parser.peek().is_some_and(|next| next != '(') {
+        parser.position += 1;
+    }
+    let raw = parser.parse_tree()?;
+
+    if raw.value("B").is_some() || raw.value("W").is_some() {
+        return Err(String::from("Moves in the root node aren't supported"));
+    }
+    if raw.value("GM").is_some_and(|game| game != "1") {
+        return Err(String::from("The file isn't a game of Go"));
+    }
+
+    let (state, unknown) = root_state(&raw)?;
+    let mut tree = GameTree::new(state);
+    let mut known: Vec<&str> = ROOT_PROPERTIES
+        .iter()
+        .chain(NODE_PROPERTIES.iter())
+        .cloned()
+        .collect();
+    known.retain(|name| !unknown.contains(name));
+    tree.root_mut().comment = raw.value("C").unwrap_or("").to_string();
+    tree.root_mut().properties = other_properties(&raw, &known);
+
+    for child in &raw.children {
+        add_raw_node(&mut tree, child)?;
+    }
+
+    return Ok(tree);
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(']', "\\]")
+}
+
+fn write_property(sgf: &mut String, name: &str, values: &[String]) {
+    if values.is_empty() {
+        return;
+    }
+
+    sgf.push_str(name);
+    for value in values {
+        sgf.push('[');
+        sgf.push_str(&escape(value));
+        sgf.push(']');
+    }
+}
+
+pub fn point_to_sgf(point: Point2<usize>) -> String {
+    let letter = |coordinate: usize| match coordinate {
+        0..=25 => (b'a' + coordinate as u8) as char,
+        _ => (b'A' + (coordinate - 26) as u8) as char,
+    };
+    format!("{}{}", letter(point.x), letter(point.y))
+}
+
+fn write_stones(sgf: &mut String, stones: &[(Point2<usize>, Option<Player>)]) {
+    for (name, stone) in [
+        ("AB", Some(Player::Black)),
+        ("AW", Some(Player::White)),
+        ("AE", None),
+    ] {
+        let points: Vec<String> = stones
+            .iter()
+            .filter(|(_, setup)| *setup == stone)
+            .map(|(point, _)| point_to_sgf(*point))
+            .collect();
+        write_property(sgf, name, &points);
+    }
+}
+
+// The game information, and the stones on the board before the first move
+fn write_root(sgf: &mut String, tree: &GameTree) {
+    let state = tree.root_state();
+    let root = tree.root();
+    let has = |name: &str| root.property(name).is_some();
+    let mut property = |name: &str, value: String| write_property(sgf, name, &[value]);
+
+    property("FF", String::from("4"));
+    property("GM", String::from("1"));
+    property("CA", String::from("UTF-8"));
+    property(
+        "SZ",
+        match state.width == state.height {
+            true => state.width.to_string(),
+            false => format!("{}:{}", state.width, state.height),
+        },
+    );
+    if !has("KM") {
+        property("KM", state.komi.to_string());
+    }
+    if state.handicap > 0 {
+        property("HA", state.handicap.to_string());
+    }
+    if !has("RU") {
+        property("RU", String::from(rules_to_sgf(state.rules)));
+    }
+
+    // The result of a game played on the device is known once it ends
+    let result = tree.current_state().result;
+    if let Some(result) = result {
+        property("RE", result.to_string());
+    }
+
+    write_stones(sgf, &root.setup);
+
+    for (name, values) in &root.properties {
+        if name != "RE" || result.is_none() {
+            write_property(sgf, name, values);
+        }
+    }
+    if !root.comment.is_empty() {
+        write_property(sgf, "C", std::slice::from_ref(&root.comment));
+    }
+}
+
+fn write_node(sgf: &mut String, node: &Node) {
+    match node.played {
+        Some(Move::Play(player, point)) => {
+            write_property(sgf, player_to_sgf(player), &[point_to_sgf(point)])
+        }
+        Some(Move::Pass(player)) => write_property(sgf, player_to_sgf(player), &[String::new()]),
+        None => {}
+    }
+    write_stones(sgf, &node.setup);
+    for (name, values) in &node.properties {
+        write_property(sgf, name, values);
+    }
+    if !node.comment.is_empty() {
+        write_property(sgf, "C", std::slice::from_ref(&node.comment));
+    }
+}
+
+pub fn player_to_sgf(player: Player) -> &'static str {
+    match player {
+        Player::Black => "B",
+        Player::White => "W",
+    }
+}
+
+// Writes a node and everything after it. A line of moves stays in one sequence, and only a
+// node with several children starts variations
+fn write_sequence(sgf: &mut String, tree: &GameTree, mut index: usize) {
+    loop {
+        sgf.push(';');
+        match index {
+            0 => write_root(sgf, tree),
+            _ => write_node(sgf, tree.node(index)),
+        }
+        sgf.push('\n');
+
+        let children = &tree.node(index).children;
+        if children.len() == 1 {
+            index = children[0];
+            continue;
+        }
+
+        for child in children {
+            sgf.push('(');
+            write_sequence(sgf, tree, *child);
+            sgf.push(')');
+        }
+        return;
+    }
+}
+
+pub fn write(tree: &GameTree) -> String {
+    let mut sgf = String::from("(");
+    write_sequence(&mut sgf, tree, 0);
+    sgf.push_str(")\n");
+    return sgf;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_tree::{GameTree, Move, Node};
+    use crate::go::{GamePhase, Player};
+    use crate::rules::RuleSet;
+    use cgmath::point2;
+
+    const OGS: &str = include_str!("../tests/fixtures/ogs.sgf");
+    const SABAKI: &str = include_str!("../tests/fixtures/sabaki.sgf");
+    const KGS: &str = include_str!("../tests/fixtures/kgs.sgf");
+
+    // Every node in the tree, in the order they were read
+    fn nodes(tree: &GameTree) -> Vec<Node> {
+        let mut nodes = vec![];
+        let mut to_visit = vec![0];
+        while let Some(index) = to_visit.pop() {
+            let node = tree.node(index);
+            nodes.push(node.clone());
+            to_visit.extend(node.children.iter().rev());
+        }
+        return nodes;
+    }
+
+    // Follows the first child of each node from the root, and returns the tree positioned there
+    fn main_line_end(mut tree: GameTree) -> GameTree {
+        while tree.go_to_child(0) {}
+        return tree;
+    }
+
+    #[test]
+    fn read_ogs() {
+        let tree = super::read(OGS).unwrap();
+        let state = tree.root_state();
+        assert_eq!((state.width, state.height), (9, 9));
+        assert_eq!(state.komi.to_string(), "6.5");
+        assert_eq!(state.rules, RuleSet::Japanese);
+        assert_eq!(tree.root().property("PB"), Some("alice"));
+        assert_eq!(tree.root().property("RE"), Some("W+R"));
+
+        let first = tree.node(tree.root().children[0]);
+        assert_eq!(first.played, Some(Move::Play(Player::Black, point2(4, 4))));
+        assert_eq!(first.children.len(), 2);
+
+        let tree = main_line_end(tree);
+        assert_eq!(tree.move_number(), 5);
+        assert_eq!(
+            tree.current_state().stone_at(point2(2, 6)),
+            Some(Player::Black)
+        );
+        let parent = tree.current_node().parent.unwrap();
+        assert_eq!(tree.node(parent).comment, "bob: nice shape");
+    }
+
+    #[test]
+    fn read_sabaki() {
+        let tree = super::read(SABAKI).unwrap();
+        let state = tree.root_state();
+        assert_eq!((state.width, state.height), (13, 13));
+        assert_eq!(state.rules, RuleSet::Chinese);
+        assert_eq!(state.handicap, 2);
+        assert_eq!(state.handicap_stones, vec![point2(9, 3), point2(3, 9)]);
+        assert_eq!(state.current_player, Player::White);
+
+        let mut tree = main_line_end(tree);
+        assert_eq!(tree.current_state().phase, GamePhase::Scoring);
+        tree.go_to_parent();
+        tree.go_to_parent();
+        tree.go_to_parent();
+        assert_eq!(
+            tree.current_node().comment,
+            "A comment with a ] bracket and a \\ backslash"
+        );
+
+        // The second variation ends with a setup node, which clears a stone and adds four more
+        assert!(tree.go_to_child(1));
+        assert!(tree.go_to_child(0));
+        assert_eq!(tree.current_node().played, None);
+        assert_eq!(tree.current_node().comment, "Study this");
+        let state = tree.current_state();
+        assert_eq!(state.stone_at(point2(9, 9)), None);
+        assert_eq!(state.stone_at(point2(1, 2)), Some(Player::White));
+    }
+
+    #[test]
+    fn read_kgs() {
+        let tree = super::read(KGS).unwrap();
+        assert_eq!(tree.root_state().komi.to_string(), "6.5");
+        assert_eq!(tree.root_state().handicap, 0);
+
+        let tree = main_line_end(tree);
+        assert_eq!(tree.move_number(), 6);
+        assert_eq!(tree.current_node().played, Some(Move::Pass(Player::White)));
+        assert_eq!(tree.current_state().phase, GamePhase::Scoring);
+        assert_eq!(
+            tree.current_node().properties[1],
+            ("TW".to_string(), vec!["aa".to_string(), "ba".to_string()])
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for (fixture, kept) in [
+            (
+                OGS,
+                vec![
+                    "PC[OGS: https://online-go.com/game/51234567]",
+                    "OT[5x30 byo-yomi]",
+                ],
+            ),
+            (SABAKI, vec!["AP[Sabaki:0.52.2]", "LB[gg:A][ff:B]", "MN[1]"]),
+            (KGS, vec!["ST[2]", "BL[1795.2]", "TW[aa][ba]", "RE[B+3.50]"]),
+        ] {
+            let tree = super::read(fixture).unwrap();
+            let written = super::write(&tree);
+            for property in kept {
+                assert!(written.contains(property), "{} was lost", property);
+            }
+
+            let reread = super::read(&written).unwrap();
+            assert_eq!(nodes(&reread), nodes(&tree));
+            assert!(reread.root_state() == tree.root_state());
+            assert_eq!(reread.root_state().komi, tree.root_state().komi);
+            assert_eq!(super::write(&reread), written);
+        }
+    }
+
+    #[test]
+    fn write_game() {
+        let mut state = crate::go::BoardState::new(9, 13);
+        state.rules = RuleSet::NewZealand;
+        state.komi = RuleSet::NewZealand.default_komi();
+        let mut tree = GameTree::new(state);
+        assert_eq!(tree.play(point2(3, 3)), Ok(()));
+        assert_eq!(tree.pass(), Ok(()));
+        tree.current_state_mut().resign(Player::Black).unwrap();
+
+        assert_eq!(
+            super::write(&tree),
+            "(;FF[4]GM[1]CA[UTF-8]SZ[9:13]KM[7]RU[NZ]RE[W+R]\n;B[dd]\n;W[]\n)\n"
+        );
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(super::read("").is_err());
+        assert!(super::read("(;SZ[19];B[zz])").is_err());
+        assert!(super::read("(;SZ[9];B[aa];W[aa])").is_err());
+        assert!(super::read("(;GM[3])").is_err());
+        assert!(super::read("(;SZ[19];B[aa]").is_err());
+        assert!(super::read("(;SZ[19]C[unclosed)").is_err());
+        assert!(super::read("(;SZ[52])").is_err());
+    }
+}
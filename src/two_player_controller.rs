@@ -1,26 +1,53 @@
+use crate::clock::GameClock;
 use crate::game_controller::{ControllerOption, GameController};
-use crate::go::BoardState;
+use crate::game_tree::GameTree;
+use crate::go::{BoardState, MoveError, Player};
+use crate::live_game::LiveGame;
+use crate::rules::RuleSet;
+use crate::settings::{Clock, GameSettings};
 use cgmath::Point2;
-use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
 
 pub struct TwoPlayerController {
-    state: BoardState,
+    game: LiveGame,
 }
 
 impl TwoPlayerController {
-    pub(crate) fn new(options: HashMap<String, String>) -> TwoPlayerController {
-        let board_size = options.get("Board Size").unwrap();
-        let initial_state = BoardState::new(board_size.split("x").next().unwrap().parse().unwrap());
+    pub(crate) fn new(
+        settings: &GameSettings,
+        directory: PathBuf,
+    ) -> Result<TwoPlayerController, String> {
+        Ok(TwoPlayerController {
+            game: LiveGame::start(settings, settings.initial_state()?, directory),
+        })
+    }
+
+    pub(crate) fn resume(tree: GameTree, directory: PathBuf) -> TwoPlayerController {
         TwoPlayerController {
-            state: initial_state,
+            game: LiveGame::resume(tree, directory),
         }
     }
 }
 
 pub fn options() -> Vec<ControllerOption> {
     vec![
-        ControllerOption::new("Board Size", vec!["9x9", "13x13", "19x19"]),
-        ControllerOption::new("Clock", vec!["Blitz", "Rapid", "None"]),
+        ControllerOption::new(
+            "Board Size",
+            vec!["5x5", "7x7", "9x9", "13x13", "19x19", "21x21"],
+        ),
+        ControllerOption::new(
+            "Rules",
+            RuleSet::ALL.iter().map(|rules| rules.name()).collect(),
+        ),
+        ControllerOption::new(
+            "Komi",
+            vec!["Auto", "-6.5", "0", "0.5", "5.5", "6.5", "7", "7.5"],
+        ),
+        ControllerOption::new(
+            "Clock",
+            Clock::ALL.iter().map(|clock| clock.name()).collect(),
+        ),
         ControllerOption::new(
             "Handicap",
             vec!["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"],
@@ -29,11 +56,65 @@ pub fn options() -> Vec<ControllerOption> {
 }
 
 impl GameController for TwoPlayerController {
+    fn game_tree(&self) -> &GameTree {
+        &self.game.tree
+    }
+
     fn current_game_state(&self) -> &BoardState {
-        &self.state
+        self.game.tree.current_state()
+    }
+
+    fn try_play(&mut self, point: Point2<usize>) -> Result<(), MoveError> {
+        self.game.play(point)
+    }
+
+    fn move_error(&self) -> Option<&MoveError> {
+        self.game.move_error.as_ref()
+    }
+
+    fn save_error(&self) -> Option<&io::Error> {
+        self.game.save_error.as_ref()
+    }
+
+    fn clock(&self) -> Option<&GameClock> {
+        self.game.clock.as_ref()
+    }
+
+    fn check_time(&mut self) {
+        self.game.check_time();
+    }
+
+    fn pass(&mut self) -> Result<(), MoveError> {
+        self.game.pass()
+    }
+
+    fn resign(&mut self) -> Result<(), &str> {
+        let player = self.game.tree.current_state().current_player;
+        self.game.resign(player)
+    }
+
+    fn toggle_handicap_stone(&mut self, point: Point2<usize>) -> Result<(), &str> {
+        self.game.toggle_handicap_stone(point)
+    }
+
+    fn confirm_handicap(&mut self) -> Result<(), &str> {
+        self.game.confirm_handicap()
+    }
+
+    fn toggle_dead(&mut self, point: Point2<usize>) -> Result<(), &str> {
+        self.game.toggle_dead(point)
+    }
+
+    fn accept_score(&mut self, player: Player) -> Result<(), &str> {
+        self.game.accept_score(player)
+    }
+
+    // Both players share the board, so moves are taken back one at a time
+    fn undo(&mut self) -> Result<(), &str> {
+        self.game.undo(|_| true)
     }
 
-    fn try_play(&mut self, point: Point2<usize>) -> Result<(), &str> {
-        self.state.try_play(point)
+    fn redo(&mut self) -> Result<(), &str> {
+        self.game.redo(|_| true)
     }
 }
@@ -1,16 +1,112 @@
 use libremarkable::appctx::ApplicationContext;
 use libremarkable::framebuffer::common::{display_temp, dither_mode, waveform_mode, DRAWING_QUANT_BIT, mxcfb_rect};
 use libremarkable::framebuffer::{FramebufferDraw, FramebufferRefresh, PartialRefreshMode};
-use libremarkable::input::InputEvent;
+use libremarkable::input::ev::EvDevContext;
+use libremarkable::input::{InputDevice, InputEvent};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::atomic::AtomicBool;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// What the event loop hands to the current scene
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiEvent {
+    Input(InputEvent),
+    // A timer scheduled on the controller has gone off
+    Timer(&'static str),
+    // Work on another thread has finished something, and passed a Waker to say so
+    Wake,
+}
+
+// What arrives on the event loop's channel, from the input thread and from wakers
+enum Message {
+    Input(InputEvent),
+    Wake,
+}
+
+struct Timer {
+    name: &'static str,
+    due: Instant,
+    interval: Duration,
+}
+
+// The timers the current scene has scheduled, each going off every interval. If the loop falls
+// behind, the missed ticks become one, so a slow refresh doesn't lead to a queue of redraws
+#[derive(Default)]
+struct Timers {
+    timers: Vec<Timer>,
+}
+
+impl Timers {
+    // A timer which is already waiting is moved rather than doubled up
+    fn schedule(&mut self, name: &'static str, interval: Duration, now: Instant) {
+        self.cancel(name);
+        self.timers.push(Timer {
+            name,
+            due: now + interval,
+            interval,
+        });
+    }
+
+    fn cancel(&mut self, name: &'static str) {
+        self.timers.retain(|timer| timer.name != name);
+    }
+
+    fn clear(&mut self) {
+        self.timers.clear();
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.timers.iter().map(|timer| timer.due).min()
+    }
+
+    // The timers which have gone off by now, each due again an interval from now
+    fn take_due(&mut self, now: Instant) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        for timer in self.timers.iter_mut().filter(|timer| timer.due <= now) {
+            names.push(timer.name);
+            timer.due = now + timer.interval;
+        }
+        return names;
+    }
+}
+
+// The events for messages taken off the channel together, with any wake-ups merged into one
+fn events_from(messages: impl Iterator<Item = Message>) -> Vec<UiEvent> {
+    let mut events = Vec::new();
+    for message in messages {
+        match message {
+            Message::Input(event) => events.push(UiEvent::Input(event)),
+            Message::Wake if !events.contains(&UiEvent::Wake) => events.push(UiEvent::Wake),
+            Message::Wake => {}
+        }
+    }
+    return events;
+}
+
+// Lets work on another thread wake the event loop, so the current scene can pick up its results
+#[derive(Clone)]
+pub struct Waker {
+    sender: Sender<Message>,
+}
+
+impl Waker {
+    pub fn wake(&self) {
+        let _ = self.sender.send(Message::Wake);
+    }
+}
 
 pub struct UiController<'a> {
     pub context: ApplicationContext<'a>,
     pub current_scene: Rc<RefCell<dyn SceneTrait>>,
     pending_scene_change: bool,
     pending_scene_change_deep_refresh: bool,
+    timers: Timers,
+    sender: Sender<Message>,
+    receiver: Receiver<Message>,
+    touch: Option<EvDevContext>,
 }
 
 impl<'a> UiController<'a> {
@@ -18,16 +114,33 @@ impl<'a> UiController<'a> {
         context: ApplicationContext,
         initial_scene: Rc<RefCell<dyn SceneTrait>>,
     ) -> UiController {
+        let (sender, receiver) = channel();
         UiController {
             context,
             current_scene: initial_scene,
             pending_scene_change: false,
             pending_scene_change_deep_refresh: false,
+            timers: Timers::default(),
+            sender,
+            receiver,
+            touch: None,
         }
     }
 
+    pub fn waker(&self) -> Waker {
+        Waker {
+            sender: self.sender.clone(),
+        }
+    }
+
+    // Sends UiEvent::Timer(name) to the current scene every interval, until the scene changes
+    pub fn schedule_repeating(&mut self, name: &'static str, interval: Duration) {
+        self.timers.schedule(name, interval, Instant::now());
+    }
+
     pub fn change_scene(self_: Rc<RefCell<&mut Self>>, new_scene: Rc<RefCell<dyn SceneTrait>>, deep_refresh: bool) {
         self_.borrow_mut().current_scene = new_scene;
+        self_.borrow_mut().timers.clear();
         self_.borrow_mut().current_scene.borrow_mut().initialize();
         self_.borrow_mut().pending_scene_change = true;
         self_.borrow_mut().pending_scene_change_deep_refresh = deep_refresh;
@@ -86,16 +199,46 @@ impl<'a> UiController<'a> {
         reset_redraw();
     }
 
+    // Waits until there is input, a wake-up or a timer due, then takes everything which is ready
+    // so it can all be handled before the screen is redrawn
+    fn next_events(&mut self) -> Vec<UiEvent> {
+        let first = match self.timers.next_due() {
+            Some(due) => {
+                let timeout = due.saturating_duration_since(Instant::now());
+                self.receiver.recv_timeout(timeout).ok()
+            }
+            None => self.receiver.recv().ok(),
+        };
+
+        let mut events = events_from(first.into_iter().chain(self.receiver.try_iter()));
+        let due = self.timers.take_due(Instant::now());
+        events.extend(due.into_iter().map(UiEvent::Timer));
+        return events;
+    }
+
     pub fn start(self_: Rc<RefCell<&mut Self>>) {
         self_.borrow_mut().current_scene.borrow_mut().initialize();
         UiController::full_refresh(self_.clone());
 
-        let context = self_.borrow_mut().context.upgrade_ref();
-        context.start_event_loop(
-            false,
-            true,
-            false,
-            |_ctx: &mut ApplicationContext, event: InputEvent| {
+        // Touch input is read on its own thread and passed into the same channel as wake-ups, so
+        // the loop only has one place to wait
+        let (input_sender, input_receiver) = channel();
+        let mut touch = EvDevContext::new(InputDevice::Multitouch, input_sender);
+        touch.start();
+        self_.borrow_mut().touch = Some(touch);
+
+        let sender = self_.borrow_mut().sender.clone();
+        thread::spawn(move || {
+            for event in input_receiver {
+                if sender.send(Message::Input(event)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        loop {
+            let events = self_.borrow_mut().next_events();
+            for event in events {
                 let scene = self_.clone().borrow_mut().current_scene.clone();
                 scene.borrow_mut().handle_event(self_.clone(), event);
 
@@ -107,14 +250,14 @@ impl<'a> UiController<'a> {
                     }
                     self_.borrow_mut().pending_scene_change = false;
                 }
+            }
 
-                while needs_redraw() {
-                    reset_redraw();
-                    let scene = self_.clone().borrow_mut().current_scene.clone();
-                    scene.borrow_mut().draw(self_.clone());
-                }
-            },
-        );
+            while needs_redraw() {
+                reset_redraw();
+                let scene = self_.clone().borrow_mut().current_scene.clone();
+                scene.borrow_mut().draw(self_.clone());
+            }
+        }
     }
 }
 
@@ -126,7 +269,7 @@ pub struct Scene<State: ?Sized> {
 pub trait SceneTrait {
     fn initialize(&mut self);
     fn draw(&self, ui: Rc<RefCell<&mut UiController>>);
-    fn handle_event(&mut self, ui: Rc<RefCell<&mut UiController>>, event: InputEvent);
+    fn handle_event(&mut self, ui: Rc<RefCell<&mut UiController>>, event: UiEvent);
 }
 
 impl<State> Scene<State> {
@@ -155,9 +298,13 @@ impl<State> SceneTrait for Scene<State> {
         }
     }
 
-    fn handle_event(&mut self, ui: Rc<RefCell<&mut UiController>>, event: InputEvent) {
+    fn handle_event(&mut self, ui: Rc<RefCell<&mut UiController>>, event: UiEvent) {
+        EVENT_HANDLED.store(false, std::sync::atomic::Ordering::SeqCst);
         for component in self.components.iter_mut() {
             component.handle_event(ui.clone(), &mut self.state, &event);
+            if EVENT_HANDLED.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                break;
+            }
         }
     }
 }
@@ -168,7 +315,7 @@ pub trait UiComponent<State: ?Sized> {
         &mut self,
         _ui: Rc<RefCell<&mut UiController>>,
         _state: &mut State,
-        _event: &InputEvent,
+        _event: &UiEvent,
     ) {
     }
     fn draw(&self, ui: Rc<RefCell<&mut UiController>>, state: &State);
@@ -187,3 +334,55 @@ pub fn needs_redraw() -> bool {
 pub fn reset_redraw() {
     NEEDS_REDRAW.store(false, std::sync::atomic::Ordering::SeqCst);
 }
+
+static EVENT_HANDLED: AtomicBool = AtomicBool::new(false);
+
+// Keeps the event from the scene's remaining components. A press a button has acted on can change
+// which buttons are shown, and must not reach one which has just appeared in the same spot
+pub fn mark_handled() {
+    EVENT_HANDLED.store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{events_from, Message, Timers, UiEvent};
+    use libremarkable::input::InputEvent;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn wakes_are_merged() {
+        let messages = vec![
+            Message::Wake,
+            Message::Input(InputEvent::Unknown {}),
+            Message::Wake,
+        ];
+        assert_eq!(
+            events_from(messages.into_iter()),
+            vec![UiEvent::Wake, UiEvent::Input(InputEvent::Unknown {})]
+        );
+    }
+
+    #[test]
+    fn missed_ticks_become_one() {
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+        let mut timers = Timers::default();
+        timers.schedule("clock", second, start);
+        timers.schedule("other", second * 10, start);
+        assert_eq!(timers.next_due(), Some(start + second));
+        assert!(timers.take_due(start).is_empty());
+
+        // Three intervals late, the timer goes off once and is next due an interval later
+        let late = start + second * 3 + second / 2;
+        assert_eq!(timers.take_due(late), vec!["clock"]);
+        assert_eq!(timers.next_due(), Some(late + second));
+
+        // Scheduling again moves the timer rather than adding a second one
+        timers.schedule("clock", second * 2, late);
+        assert_eq!(timers.take_due(late + second * 2), vec!["clock"]);
+        timers.cancel("clock");
+        assert_eq!(timers.next_due(), Some(start + second * 10));
+        timers.clear();
+        assert_eq!(timers.next_due(), None);
+    }
+}
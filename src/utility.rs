@@ -8,3 +8,9 @@ macro_rules! vec_of_strings {
 }
 
 pub(crate) use vec_of_strings;
+
+// The home directory of the user running the app, which is root's on the reMarkable
+pub fn home_directory() -> std::path::PathBuf {
+    let home = std::env::var_os("HOME").unwrap_or_else(|| "/home/root".into());
+    return std::path::PathBuf::from(home);
+}